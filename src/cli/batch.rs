@@ -0,0 +1,246 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+use anyhow::Result;
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::convert::{convert_file, ConvertOptions, Converted};
+use super::exit;
+use super::json;
+use super::Failure;
+
+/// Options for converting several inputs in one run
+#[derive(Args, Clone, Default)]
+pub struct BatchArgs {
+    /// Also scan the subdirectories of directory inputs
+    #[arg(short, long)]
+    pub recursive: bool,
+
+    /// Convert this many files at once; 0 uses every CPU core
+    #[arg(short, long, value_name = "N", default_value_t = 1)]
+    pub jobs: usize,
+}
+
+/// Output paths already taken in a batch, so two inputs never write the same file
+/// and no input is overwritten by another input's output
+pub struct Claims {
+    owners: Mutex<HashMap<PathBuf, String>>,
+}
+
+impl Claims {
+    fn new(inputs: &[String]) -> Self {
+        let owners = inputs.iter().map(|input| (normalize(input), input.clone())).collect();
+        Claims { owners: Mutex::new(owners) }
+    }
+
+    /// Reserves `out_path` for `input`, refusing if another input (or its output) is already there
+    pub fn claim(&self, input: &str, out_path: &str) -> std::result::Result<(), Failure> {
+        let mut owners = self.owners.lock().unwrap_or_else(|e| e.into_inner());
+        let key = normalize(out_path);
+        match owners.get(&key) {
+            Some(owner) if owner != input => {
+                let taken = if normalize(owner) == key {
+                    "an input of this batch".to_string()
+                } else {
+                    format!("the output of {}", owner)
+                };
+                Err(Failure::Refused(format!(
+                    "Refusing to write {}: it is {}; use --output-dir or --name-template to keep outputs apart.",
+                    out_path, taken
+                )))
+            }
+            _ => {
+                owners.insert(key, input.to_string());
+                Ok(())
+            }
+        }
+    }
+}
+
+pub fn has_directory(paths: &[String]) -> bool {
+    paths.iter().any(|p| Path::new(p).is_dir())
+}
+
+/// Converts every input independently, then prints a summary; exits non-zero if any input was not converted
+pub fn run(paths: &[String], options: &ConvertOptions) -> Result<()> {
+    if options.output.output.is_some() {
+        eprintln!("--output names a single file; use --output-dir or --name-template with several inputs.");
+        std::process::exit(exit::USAGE);
+    }
+
+    let inputs = collect_inputs(paths, options.batch.recursive)?;
+    if inputs.is_empty() {
+        if !options.json {
+            println!("No saves found.");
+        }
+        return Ok(());
+    }
+
+    let jobs = match options.batch.jobs {
+        0 => thread::available_parallelism().map(|n| n.get()).unwrap_or(1),
+        n => n,
+    }.min(inputs.len());
+
+    let claims = Claims::new(&inputs);
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<std::result::Result<Converted, Failure>>>> =
+        Mutex::new(inputs.iter().map(|_| None).collect());
+    let stdout = Mutex::new(());
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                let Some(input) = inputs.get(i) else { break };
+
+                let mut log = Vec::new();
+                let result = convert_file(input, options, Some(&claims), &mut log);
+
+                // Keep each file's lines together when several files are converted at once
+                let _guard = stdout.lock().unwrap_or_else(|e| e.into_inner());
+                if options.json {
+                    match &result {
+                        Ok(c) => println!("{}", json::converted(input, c)),
+                        Err(f) => println!("{}", json::failure(input, f)),
+                    }
+                } else {
+                    println!("[{}/{}] {}", i + 1, inputs.len(), input);
+                    for line in &log {
+                        println!("  {}", line);
+                    }
+                    match &result {
+                        Ok(c) if c.dry_run => println!("  {} → {} (dry run, nothing written)", c.direction(), c.out_path),
+                    Ok(c) => println!("  {} → {}", c.direction(), c.out_path),
+                        Err(f) => println!("  {}", f.message()),
+                    }
+                }
+                results.lock().unwrap_or_else(|e| e.into_inner())[i] = Some(result);
+            });
+        }
+    });
+
+    let results: Vec<_> = results.into_inner().unwrap_or_else(|e| e.into_inner())
+        .into_iter()
+        .map(|r| r.expect("every input is processed"))
+        .collect();
+    // JSON consumers get one object per input and no summary table
+    if !options.json {
+        print_summary(&inputs, &results);
+    }
+    let code = batch_exit_code(&results);
+    if code != exit::OK {
+        std::process::exit(code);
+    }
+    Ok(())
+}
+
+/// Files named on the command line as-is, plus the save-sized files inside directory inputs
+fn collect_inputs(paths: &[String], recursive: bool) -> Result<Vec<String>> {
+    let mut inputs = Vec::new();
+    for path in paths {
+        if Path::new(path).is_dir() {
+            let mut found = Vec::new();
+            scan_dir(Path::new(path), recursive, &mut found)?;
+            found.sort();
+            inputs.extend(found.into_iter().map(|p| p.to_string_lossy().to_string()));
+        } else {
+            // Missing files are kept so they show up as failures in the summary
+            inputs.push(path.clone());
+        }
+    }
+    Ok(inputs)
+}
+
+fn scan_dir(dir: &Path, recursive: bool, found: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            if recursive {
+                scan_dir(&path, recursive, found)?;
+            }
+        } else if file_type.is_file() && is_candidate(&path, entry.metadata()?.len()) {
+            found.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Whether a file met while scanning could be a save: right size for a known layout, and not a backup or temp file
+pub fn is_candidate(path: &Path, len: u64) -> bool {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    if name.contains(".bak-") || name.contains(".tmp-") {
+        return false;
+    }
+    xv2::KNOWN_LAYOUTS.iter()
+        .any(|l| len == l.ps4_size as u64 || len == l.editor_size as u64)
+}
+
+/// Absolute form of `path` for comparing outputs, even if it does not exist yet
+fn normalize(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if let Ok(p) = fs::canonicalize(path) {
+        return p;
+    }
+    let parent = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    match (fs::canonicalize(parent), path.file_name()) {
+        (Ok(dir), Some(name)) => dir.join(name),
+        _ => std::env::current_dir().map(|d| d.join(path)).unwrap_or_else(|_| path.to_path_buf()),
+    }
+}
+
+/// 0 if every input was converted, the failures' code if they all share one, otherwise 1
+fn batch_exit_code(results: &[std::result::Result<Converted, Failure>]) -> i32 {
+    let mut codes = results.iter().filter_map(|r| r.as_ref().err()).map(|f| f.exit_code());
+    match codes.next() {
+        None => exit::OK,
+        Some(first) if codes.all(|c| c == first) => first,
+        Some(_) => exit::FAILURE,
+    }
+}
+
+/// Prints one row per input and the totals
+fn print_summary(inputs: &[String], results: &[std::result::Result<Converted, Failure>]) {
+    let width = inputs.iter().map(|i| i.chars().count()).max().unwrap_or(0).max("INPUT".len());
+
+    println!();
+    println!("{:<8} {:<9} {:<10} {:<width$}  OUTPUT / REASON", "STATUS", "DIRECTION", "LEFTOVERS", "INPUT");
+    let (mut converted, mut refused, mut failed) = (0, 0, 0);
+    let (mut with_leftovers, mut leftover_bytes) = (0, 0);
+    for (input, result) in inputs.iter().zip(results) {
+        match result {
+            Ok(c) => {
+                converted += 1;
+                let produced = match (&c.leftovers, c.from) {
+                    (Some((_, len)), xv2::SaveFormat::Ps4) => {
+                        with_leftovers += 1;
+                        leftover_bytes += len;
+                        format!("0x{:X}", len)
+                    }
+                    (Some((_, len)), xv2::SaveFormat::PcReady) => format!("used 0x{:X}", len),
+                    (None, _) if c.embedded => "in gap".to_string(),
+                    (None, _) => "-".to_string(),
+                };
+                let status = if c.dry_run { "DRY-RUN" } else { "OK" };
+                println!("{:<8} {:<9} {:<10} {:<width$}  {}", status, c.direction(), produced, input, c.out_path);
+            }
+            Err(f) => {
+                let status = match f {
+                    Failure::NotFound(_) => { failed += 1; "MISSING" }
+                    Failure::Refused(_) | Failure::Unrecognized { .. } => { refused += 1; "REFUSED" }
+                    Failure::Error(_) => { failed += 1; "FAILED" }
+                };
+                println!("{:<8} {:<9} {:<10} {:<width$}  {}", status, "-", "-", input, f.message());
+            }
+        }
+    }
+    println!();
+    println!("{} converted, {} refused, {} failed; leftovers produced for {} save(s) (0x{:X} bytes)",
+             converted, refused, failed, with_leftovers, leftover_bytes);
+}
@@ -0,0 +1,380 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, ValueEnum};
+use xv2_converter_lib as xv2;
+
+use super::batch::{self, BatchArgs, Claims};
+use super::json;
+use super::output::OutputArgs;
+use super::{describe_provenance, exit, file_name, leftovers_store, load_save, lookup_store, report_md5, Failure, LeftoversArgs, STDIO};
+
+#[derive(Args)]
+pub struct ConvertArgs {
+    /// Input files or directories - PS4 save files (with 0x20 MD5 header + 0x80 #SAV section) or PC-ready save files
+    #[arg(required = true)]
+    pub input_files: Vec<String>,
+
+    #[command(flatten)]
+    pub options: ConvertOptions,
+}
+
+/// Conversion flags, also accepted without the `convert` subcommand
+#[derive(Args)]
+pub struct ConvertOptions {
+    /// Which way to convert; a forced direction refuses input of the other format
+    #[arg(long, value_enum, default_value_t = Direction::Auto)]
+    pub direction: Direction,
+
+    #[command(flatten)]
+    pub output: OutputArgs,
+
+    #[command(flatten)]
+    pub leftovers: LeftoversArgs,
+
+    #[command(flatten)]
+    pub batch: BatchArgs,
+
+    /// What to do when a PC-ready save flags leftovers but they are missing or too short
+    #[arg(long, value_enum, value_name = "POLICY", default_value_t = MissingLeftovers::Strict)]
+    pub missing_leftovers: MissingLeftovers,
+
+    /// Check whether the MD5 header of the PS4 input/output matches the body it is assumed to cover (unverified)
+    #[arg(long)]
+    pub check_md5: bool,
+
+    /// Run the conversion in memory and report what would be written, without touching any file
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Print one JSON object per input (one per line) instead of human-readable text
+    #[arg(long)]
+    pub json: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Direction {
+    /// PS4 to PC-ready
+    Ps4topc,
+    /// PC-ready to PS4
+    Pctops4,
+    /// Pick from the detected format
+    Auto,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum MissingLeftovers {
+    /// Refuse to unpack
+    Strict,
+    /// Fill the missing bytes with zeros; the PS4 save is then not the original
+    ZeroFill,
+    /// Ask on the terminal whether to zero-fill (strict when there is no terminal)
+    Prompt,
+}
+
+/// Asks on the terminal whether to zero-fill the missing leftovers of `input`; false without a terminal
+fn confirm_zero_fill(input: &str, e: &xv2::ConversionError) -> bool {
+    use std::io::IsTerminal;
+    // Batch jobs may run in parallel; one question at a time
+    static PROMPT: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+    let _guard = PROMPT.lock().unwrap_or_else(|e| e.into_inner());
+    eprint!("{}: {} Fill the missing bytes with zeros? The PS4 save will not be the original. [y/N] ", file_name(input), e);
+    let mut answer = String::new();
+    if std::io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim(), "y" | "Y" | "yes")
+}
+
+/// What converting one input produced
+pub struct Converted {
+    pub from: xv2::SaveFormat,
+    pub layout: &'static xv2::SaveLayout,
+    pub out_path: String,
+    pub input_size: usize,
+    pub output_size: usize,
+    pub input_sha1: String,
+    pub output_sha1: String,
+    /// Leftovers flag of the PC-ready side's marker (the output when packing, the input when unpacking)
+    pub marker_flag: Option<u8>,
+    /// Where the leftovers went (packing) or came from (unpacking), and how many bytes
+    pub leftovers: Option<(String, usize)>,
+    /// The HCD tail is stored in the PC-ready save's fill gap instead
+    pub embedded: bool,
+    pub backups: Vec<xv2::Backup>,
+    pub warnings: Vec<String>,
+    /// Fill, trim and pad sizes of the layout
+    pub geometry: xv2::PackGeometry,
+    /// The output existed before the conversion (and was backed up, or would be in a dry run)
+    pub overwrites: bool,
+    /// Nothing was written
+    pub dry_run: bool,
+}
+
+impl Converted {
+    pub fn direction(&self) -> &'static str {
+        match self.from {
+            xv2::SaveFormat::Ps4 => "PS4→PC",
+            xv2::SaveFormat::PcReady => "PC→PS4",
+        }
+    }
+}
+
+pub fn run(args: ConvertArgs) -> Result<()> {
+    let options = args.options;
+    if args.input_files.len() > 1 || batch::has_directory(&args.input_files) {
+        if args.input_files.iter().any(|i| i == STDIO) {
+            eprintln!("stdin ('-') can only be converted on its own.");
+            std::process::exit(exit::USAGE);
+        }
+        return batch::run(&args.input_files, &options);
+    }
+
+    let input = &args.input_files[0];
+
+    // With the save going to stdout, everything else goes to stderr
+    let to_stdout = options.output.writes_stdout(input) && !options.dry_run;
+    if to_stdout && options.json {
+        eprintln!("--json needs the converted save to go to a file; use --output or --output-dir.");
+        std::process::exit(exit::USAGE);
+    }
+    let say = |line: &str| if to_stdout { eprintln!("{}", line) } else { println!("{}", line) };
+
+    let mut log = Vec::new();
+    let result = convert_file(input, &options, None, &mut log);
+    if options.json {
+        match result {
+            Ok(converted) => println!("{}", json::converted(input, &converted)),
+            Err(failure) => {
+                println!("{}", json::failure(input, &failure));
+                std::process::exit(failure.exit_code());
+            }
+        }
+        return Ok(());
+    }
+
+    for line in &log {
+        say(line);
+    }
+    let converted = result.map_err(Failure::exit)?;
+
+    if converted.dry_run {
+        let existing = if converted.overwrites { " (exists, would be backed up)" } else { "" };
+        say(&format!("DRY RUN {} → {}{}; nothing written", converted.direction(), converted.out_path, existing));
+    } else if converted.out_path == STDIO {
+        say(&format!("{} → stdout", converted.direction()));
+    } else {
+        say(&format!("{} → {}", converted.direction(), file_name(&converted.out_path)));
+    }
+    say(&format!("Input  SHA1: {}", converted.input_sha1));
+    say(&format!("Output SHA1: {}", converted.output_sha1));
+
+    Ok(())
+}
+
+/// Converts one input into its output location, appending progress lines to `log`.
+/// `claims` (batch runs) refuses outputs another input of the same run already uses.
+pub fn convert_file(
+    input_path: &str,
+    args: &ConvertOptions,
+    claims: Option<&Claims>,
+    log: &mut Vec<String>,
+) -> std::result::Result<Converted, Failure> {
+    let (data, detected) = load_save(input_path)?;
+
+    let refused = match args.direction {
+        Direction::Ps4topc if detected.format != xv2::SaveFormat::Ps4 => Some("pack"),
+        Direction::Pctops4 if detected.format != xv2::SaveFormat::PcReady => Some("unpack"),
+        _ => None,
+    };
+    if let Some(action) = refused {
+        return Err(Failure::Refused(format!("Refusing to {}: input is a {} save.", action, detected.format.name())));
+    }
+    log.push(format!("Detected {} save ({} layout)", detected.format.name(), detected.layout.name));
+    let geometry = xv2::pack_geometry(detected.layout)?;
+
+    let (out, out_path, leftovers, backups, overwrites) = match detected.format {
+        xv2::SaveFormat::Ps4 => {
+            if args.check_md5 {
+                log.push(report_md5("Input ", &data)?);
+            }
+
+            // Convert PS4 save format [MD5_HEADER][SAV_HEADER][middle][Z_BYTE] to PC-ready format [processed][Z_BYTE][SAV_HEADER][MD5_HEADER]
+            let out = xv2::ps4_to_pcready(&data)?;
+            let out_path = args.output.resolve(input_path, xv2::SaveFormat::PcReady, &out.data)?;
+            if let Some(claims) = claims {
+                claims.claim(input_path, &out_path)?;
+            }
+
+            // Leftovers of a piped save need an explicit location
+            let store = leftovers_store(&args.leftovers, &out_path);
+            if out.leftovers.is_some() && store.is_none() {
+                return Err(Failure::Refused(
+                    "Refusing to write the PC-ready save to stdout: its leftovers need --leftovers FILE, --leftovers-dir or --vault."
+                        .to_string(),
+                ));
+            }
+
+            // A sidecar left next to an overwritten PC-ready save belongs to it, so it is backed up along with it
+            let sidecar = match (&args.leftovers.leftovers_file, &args.leftovers.leftovers_dir) {
+                (Some(file), _) => Some(PathBuf::from(file)),
+                (None, _) if args.leftovers.vault.is_some() => None,
+                (None, Some(_)) => None,
+                (None, None) if out_path == STDIO => None,
+                (None, None) => Some(xv2::SidecarStore::for_pcready(&out_path).path().to_path_buf()),
+            };
+            let overwrites = out_path != STDIO && Path::new(&out_path).exists();
+            let backups = match args.dry_run {
+                true => args.output.check_clobber(&out_path).map(|_| Vec::new())?,
+                false => args.output.prepare(&out_path, sidecar.as_deref())?,
+            };
+
+            let mut held = Vec::new();
+            match (out.embedded, &out.leftovers) {
+                (true, _) => held.push("leftovers record"),
+                (false, Some(_)) => held.push("vault key"),
+                _ => {}
+            }
+            if xv2::check_provenance(&out.data).is_ok_and(|c| c.record.is_some()) {
+                held.push("provenance record");
+            }
+            match held.is_empty() {
+                true => log.push(format!("Fill: 0x{:X} zero bytes inserted before the HCD", geometry.fill_len)),
+                false => log.push(format!("Fill: 0x{:X} bytes inserted before the HCD ({}, zeros elsewhere)", geometry.fill_len, held.join(", "))),
+            }
+            if geometry.excess > 0 {
+                let kept = match (out.embedded, &out.leftovers) {
+                    (true, _) => "non-zero, stored in the fill gap",
+                    (false, Some(_)) => "non-zero, kept as leftovers",
+                    (false, None) => "all zero, dropped",
+                };
+                log.push(format!("HCD tail: 0x{:X} bytes trimmed ({})", geometry.excess, kept));
+            }
+
+            // Non-zero HCD tail bytes removed while packing go to the leftovers store (sidecar next to the PC-ready output by default)
+            let mut leftovers = None;
+            if let (Some(lf), Some(mut store)) = (&out.leftovers, store) {
+                let key = xv2::LeftoversKey { ps4_sha1: Some(xv2::sha1_hex(&data)), ..xv2::leftovers_key(&out.data) };
+                if args.dry_run {
+                    log.push(format!("LEFTOVERS would go to {} (0x{:X} bytes)", store.describe(&key), geometry.excess));
+                } else {
+                    store.store(&key, lf)
+                        .with_context(|| format!("Failed to write leftovers: {}", store.describe(&key)))?;
+                    log.push(format!("LEFTOVERS → {} (0x{:X} bytes)", store.describe(&key), geometry.excess));
+                }
+                leftovers = Some((store.describe(&key), geometry.excess));
+            }
+            (out, out_path, leftovers, backups, overwrites)
+        }
+        xv2::SaveFormat::PcReady => {
+            // Leftovers written by a previous PS4→PC run are looked up in the same store
+            let mut store = lookup_store(&args.leftovers, input_path);
+            let flagged = xv2::try_read_marker_at(&data, detected.layout.marker_offset)
+                .is_some_and(|(_, flag)| flag == xv2::FLAG_LEFTOVERS);
+            if flagged && store.is_none() && args.missing_leftovers != MissingLeftovers::ZeroFill {
+                return Err(Failure::Refused(
+                    "Refusing to unpack from stdin: the marker flags leftovers; give them with --leftovers FILE, --leftovers-dir or --vault."
+                        .to_string(),
+                ));
+            }
+
+            // Convert PC-ready format [processed][Z_BYTE][SAV_HEADER][MD5_HEADER] back to PS4 format [MD5_HEADER][SAV_HEADER][middle][Z_BYTE]
+            let policy = match args.missing_leftovers {
+                MissingLeftovers::ZeroFill => xv2::LeftoversPolicy::ZeroFill,
+                MissingLeftovers::Strict | MissingLeftovers::Prompt => xv2::LeftoversPolicy::Strict,
+            };
+            let unpack = |store: Option<&mut Box<dyn xv2::LeftoversStore>>, policy| match store {
+                Some(store) => xv2::convert_auto_with_store_and_policy(&data, store.as_mut(), policy),
+                None => xv2::convert_auto_with_policy(&data, None, policy),
+            };
+            let out = match unpack(store.as_mut(), policy) {
+                Err(e @ (xv2::ConversionError::LeftoversMissing
+                | xv2::ConversionError::LeftoversShort { .. }
+                | xv2::ConversionError::EmbeddedLeftoversCorrupt(_)))
+                    if args.missing_leftovers == MissingLeftovers::Prompt =>
+                {
+                    if !confirm_zero_fill(input_path, &e) {
+                        return Err(e.into());
+                    }
+                    unpack(store.as_mut(), xv2::LeftoversPolicy::ZeroFill)?
+                }
+                result => result?,
+            };
+            if let Ok(check) = xv2::check_provenance(&data) {
+                log.push(format!("Provenance: {}", describe_provenance(&check)));
+            }
+            let mut leftovers = None;
+            if out.embedded {
+                log.push("v3 unpack: HCD tail read from the fill gap".to_string());
+            } else if let (Some(lf), Some(store)) = (&out.leftovers, &store) {
+                let key = xv2::leftovers_key(&data);
+                log.push(format!("v2 unpack: used leftovers {} (0x{:X} bytes)", store.describe(&key), lf.len()));
+                leftovers = Some((store.describe(&key), lf.len()));
+            }
+            if args.check_md5 {
+                log.push(report_md5("Output", &out.data)?);
+            }
+            let out_path = args.output.resolve(input_path, xv2::SaveFormat::Ps4, &out.data)?;
+            if let Some(claims) = claims {
+                claims.claim(input_path, &out_path)?;
+            }
+            let overwrites = out_path != STDIO && Path::new(&out_path).exists();
+            let backups = match args.dry_run {
+                true => args.output.check_clobber(&out_path).map(|_| Vec::new())?,
+                false => args.output.prepare(&out_path, None)?,
+            };
+
+            if geometry.excess > 0 {
+                let restored = match (out.embedded, &out.leftovers) {
+                    (true, _) => "restored from the fill gap",
+                    (false, Some(_)) => "restored from leftovers",
+                    (false, None) => "zero-filled",
+                };
+                log.push(format!("HCD tail: 0x{:X} bytes {}", geometry.excess, restored));
+            }
+            (out, out_path, leftovers, backups, overwrites)
+        }
+    };
+
+    for backup in &backups {
+        log.push(format!("BACKUP → {}", backup.path.display()));
+    }
+    log.extend(out.warnings.iter().cloned());
+
+    if args.dry_run {
+        // Nothing is written
+    } else if out_path == STDIO {
+        let mut stdout = std::io::stdout().lock();
+        stdout.write_all(&out.data).and_then(|_| stdout.flush())
+            .context("Failed to write output to stdout")?;
+    } else {
+        xv2::write_output_file_with(&out_path, &out.data, args.output.overwrite())?;
+    }
+
+    let pcready = match detected.format {
+        xv2::SaveFormat::Ps4 => &out.data,
+        xv2::SaveFormat::PcReady => &data,
+    };
+    Ok(Converted {
+        from: detected.format,
+        layout: detected.layout,
+        out_path,
+        input_size: data.len(),
+        output_size: out.data.len(),
+        input_sha1: xv2::sha1_hex(&data),
+        output_sha1: xv2::sha1_hex(&out.data),
+        marker_flag: xv2::try_read_marker_at(pcready, detected.layout.marker_offset).map(|(_, flag)| flag),
+        leftovers,
+        embedded: out.embedded,
+        backups,
+        warnings: out.warnings,
+        geometry,
+        overwrites,
+        dry_run: args.dry_run,
+    })
+}
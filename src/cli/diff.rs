@@ -0,0 +1,35 @@
+use anyhow::Result;
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::{exit, print_ranges, read_save};
+
+#[derive(Args)]
+pub struct DiffArgs {
+    /// First save
+    pub a: String,
+    /// Second save, of the same format as the first
+    pub b: String,
+}
+
+/// Prints the differing ranges of two saves labelled by region; exits non-zero if they differ
+pub fn run(args: DiffArgs) -> Result<()> {
+    let (a, detected_a) = read_save(&args.a)?;
+    let (b, detected_b) = read_save(&args.b)?;
+
+    if detected_a.format != detected_b.format {
+        eprintln!("Refusing to diff: {} is a {} save but {} is a {} save.",
+                  args.a, detected_a.format.name(), args.b, detected_b.format.name());
+        std::process::exit(exit::REFUSED);
+    }
+
+    let diffs = xv2::diff_regions(&a, &b, detected_a.format, detected_a.layout);
+    if diffs.is_empty() {
+        println!("Identical {} saves ({})", detected_a.format.name(), xv2::sha1_hex(&a));
+        return Ok(());
+    }
+
+    println!("{} saves differ in {} range(s):", detected_a.format.name(), diffs.len());
+    print_ranges(&diffs);
+    std::process::exit(exit::MISMATCH);
+}
@@ -0,0 +1,35 @@
+use anyhow::Result;
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::{exit, read_input, Failure};
+
+#[derive(Args)]
+pub struct DoctorArgs {
+    /// File that was not recognized as a save (`-` for stdin)
+    pub input_file: String,
+}
+
+/// Prints each finding with its suggestion; exits with the code a conversion of the file would have
+pub fn run(args: DoctorArgs) -> Result<()> {
+    let data = read_input(&args.input_file).map_err(Failure::exit)?;
+    let diagnosis = xv2::doctor(&data);
+
+    if let Some(detected) = diagnosis.detected {
+        println!("{}: {} save ({} layout), nothing to fix.", args.input_file, detected.format.name(), detected.layout.name);
+        return Ok(());
+    }
+
+    println!("{}: not recognized (0x{:X} bytes)", args.input_file, data.len());
+    for finding in &diagnosis.findings {
+        println!();
+        println!("[{}] {}", finding.check.name(), finding.summary);
+        println!("  → {}", finding.suggestion);
+    }
+
+    let code = match xv2::detect(&data) {
+        Err(e) => exit::for_unrecognized(&data, &e),
+        Ok(_) => exit::FAILURE,
+    };
+    std::process::exit(code);
+}
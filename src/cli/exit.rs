@@ -0,0 +1,61 @@
+//! Process exit codes. These are part of the CLI's interface: keep them stable and in sync with the README.
+
+use xv2_converter_lib as xv2;
+
+/// Everything requested was done
+pub const OK: i32 = 0;
+/// Unexpected or internal error not covered below
+pub const FAILURE: i32 = 1;
+/// Bad command line (also what clap uses for argument errors)
+pub const USAGE: i32 = 2;
+/// Input file (or requested backup) does not exist
+pub const NOT_FOUND: i32 = 3;
+/// Reading or writing a file failed
+pub const IO: i32 = 4;
+/// Input was recognized but the tool declined to process it (forced direction, --no-clobber, output collision)
+pub const REFUSED: i32 = 5;
+/// Input size matches no known layout
+pub const WRONG_SIZE: i32 = 10;
+/// Size is right but '#SAV' is missing where the layout needs it
+pub const MISSING_MAGIC: i32 = 11;
+/// PC-ready marker is missing, unknown or inconsistent with the layout
+pub const UNKNOWN_MARKER: i32 = 12;
+/// Leftovers are missing, too short, or could not be stored or loaded
+pub const LEFTOVERS: i32 = 13;
+/// verify or diff found differences
+pub const MISMATCH: i32 = 14;
+
+/// Code for a conversion error
+pub fn for_conversion_error(e: &xv2::ConversionError) -> i32 {
+    use xv2::ConversionError as E;
+    match e {
+        E::WrongSize { .. } => WRONG_SIZE,
+        E::MissingMagic { .. } => MISSING_MAGIC,
+        E::NoMatchingLayout { size, .. } => {
+            let size_known = xv2::KNOWN_LAYOUTS.iter().any(|l| l.ps4_size == *size || l.editor_size == *size);
+            if size_known { MISSING_MAGIC } else { WRONG_SIZE }
+        }
+        E::UnrecognizedMarker | E::UnsupportedVersion(_) | E::MarkerLayoutMismatch => UNKNOWN_MARKER,
+        E::LeftoversMissing | E::LeftoversShort { .. } | E::LeftoversStorage(_)
+        | E::LeftoversMismatch(_) | E::EmbeddedLeftoversCorrupt(_) => LEFTOVERS,
+        E::InvalidLayout(_) | E::SizeMismatch { .. } => FAILURE,
+    }
+}
+
+/// Code for a save `detect` did not recognize; a PC-ready-sized file without a valid marker counts as a marker problem
+pub fn for_unrecognized(data: &[u8], e: &xv2::ConversionError) -> i32 {
+    let marker_missing = xv2::KNOWN_LAYOUTS.iter()
+        .any(|l| l.editor_size == data.len() && xv2::try_read_marker_at(data, l.marker_offset).is_none());
+    if marker_missing { UNKNOWN_MARKER } else { for_conversion_error(e) }
+}
+
+/// Code for an error that reached `main`
+pub fn for_error(e: &anyhow::Error) -> i32 {
+    if let Some(e) = e.downcast_ref::<xv2::ConversionError>() {
+        for_conversion_error(e)
+    } else if e.downcast_ref::<std::io::Error>().is_some() {
+        IO
+    } else {
+        FAILURE
+    }
+}
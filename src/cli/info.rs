@@ -0,0 +1,52 @@
+use anyhow::Result;
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::{describe_provenance, read_save, report_md5};
+
+#[derive(Args)]
+pub struct InfoArgs {
+    /// Save file to describe
+    pub input_file: String,
+}
+
+/// Prints what `inspect` found out about the save
+pub fn run(args: InfoArgs) -> Result<()> {
+    let (data, _) = read_save(&args.input_file)?;
+    let info = xv2::inspect(&data)?;
+
+    println!("Format:         {}", info.format.name());
+    println!("Layout:         {}", info.layout.name);
+    println!("Size:           0x{:X} ({} bytes)", info.size, info.size);
+    if let (Some(version), Some(flag)) = (info.marker_version, info.leftovers_flag) {
+        println!("Marker:         version 0x{:02X}, leftovers flag 0x{:02X}", version, flag);
+    }
+    println!("Z byte:         0x{:02X}", info.z_byte);
+    let offsets: Vec<String> = info.sav_offsets.iter().map(|o| format!("0x{:X}", o)).collect();
+    println!("'#SAV' at:      {}", offsets.join(", "));
+    println!("Fill:           0x{:X} bytes", info.fill_len);
+    println!("HCD excess:     0x{:X} bytes", info.excess);
+    println!("HCD pad:        0x{:X} bytes", info.pad);
+    let leftovers = match (info.format, info.leftovers, info.embedded) {
+        (xv2::SaveFormat::Ps4, _, true) => "none (trimmed tail fits in the fill gap)",
+        (xv2::SaveFormat::Ps4, true, _) => "packing produces leftovers",
+        (xv2::SaveFormat::Ps4, false, _) => "none (trimmed tail is zero)",
+        (xv2::SaveFormat::PcReady, _, true) => "not needed (stored in the fill gap)",
+        (xv2::SaveFormat::PcReady, true, _) => "unpacking needs leftovers",
+        (xv2::SaveFormat::PcReady, false, _) => "not needed",
+    };
+    println!("Leftovers:      {}", leftovers);
+    if let Some(check) = &info.provenance {
+        println!("Provenance:     {}", describe_provenance(check));
+    }
+    if info.format == xv2::SaveFormat::Ps4 {
+        println!("{}", report_md5("PS4", &data)?);
+    }
+    println!("SHA1:           {}", info.sha1);
+    match (&info.converted_sha1, &info.conversion_error) {
+        (Some(sha1), _) => println!("Converted SHA1: {}", sha1),
+        (None, Some(e)) => println!("Converted SHA1: unavailable, the save does not convert: {}", e),
+        (None, None) => println!("Converted SHA1: unknown, depends on the leftovers kept outside the save"),
+    }
+    Ok(())
+}
@@ -0,0 +1,67 @@
+use serde_json::{json, Value};
+use xv2_converter_lib as xv2;
+
+use super::convert::Converted;
+use super::Failure;
+
+/// Object describing one converted input
+pub fn converted(input: &str, c: &Converted) -> Value {
+    let direction = match c.from {
+        xv2::SaveFormat::Ps4 => "ps4topc",
+        xv2::SaveFormat::PcReady => "pctops4",
+    };
+    let leftovers = match &c.leftovers {
+        Some((path, bytes)) => json!({ "path": path, "bytes": bytes }),
+        None => Value::Null,
+    };
+    let backups: Vec<String> = c.backups.iter().map(|b| b.path.to_string_lossy().to_string()).collect();
+
+    json!({
+        "ok": true,
+        "input": input,
+        "output": c.out_path,
+        "direction": direction,
+        "layout": c.layout.name,
+        "input_size": c.input_size,
+        "output_size": c.output_size,
+        "input_sha1": c.input_sha1,
+        "output_sha1": c.output_sha1,
+        "marker_flag": c.marker_flag,
+        "leftovers": leftovers,
+        "embedded": c.embedded,
+        "backups": backups,
+        "warnings": c.warnings,
+        "fill_len": c.geometry.fill_len,
+        "trimmed": c.geometry.excess,
+        "pad": c.geometry.pad,
+        "overwrites": c.overwrites,
+        "dry_run": c.dry_run,
+    })
+}
+
+/// Object describing an input that was not converted, with a typed error
+pub fn failure(input: &str, f: &Failure) -> Value {
+    json!({
+        "ok": false,
+        "input": input,
+        "error": {
+            "kind": error_kind(f),
+            "message": f.message(),
+        },
+        "exit_code": f.exit_code(),
+    })
+}
+
+/// `ConversionError::kind` where the failure carries one, otherwise the failure class
+fn error_kind(f: &Failure) -> &'static str {
+    match f {
+        Failure::NotFound(_) => "not_found",
+        Failure::Unrecognized { error, .. } => error.kind(),
+        Failure::Refused(_) => "refused",
+        Failure::Error(e) => match e.downcast_ref::<xv2::ConversionError>() {
+            Some(e) => e.kind(),
+            None if e.downcast_ref::<std::io::Error>().is_some() => "io",
+            None => "error",
+        },
+    }
+}
@@ -0,0 +1,272 @@
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use xv2_converter_lib::{self as xv2, LeftoversStore};
+
+mod batch;
+mod convert;
+mod diff;
+mod doctor;
+pub mod exit;
+mod info;
+mod json;
+mod output;
+mod restore;
+mod split;
+mod verify;
+mod watch;
+
+#[derive(Parser)]
+#[command(name = "XV2_PS4toPC")]
+#[command(about = "Converts Xenoverse 2 save files between PS4 and PC formats")]
+#[command(args_conflicts_with_subcommands = true, arg_required_else_help = true)]
+pub struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+
+    /// Input files or directories; without a subcommand they are converted (keeps drag-and-drop working)
+    input_files: Vec<String>,
+
+    #[command(flatten)]
+    convert: convert::ConvertOptions,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Convert a save to the other format (the default when no subcommand is given)
+    Convert(convert::ConvertArgs),
+    /// Describe a save without converting it
+    Info(info::InfoArgs),
+    /// Convert there and back in memory and report any difference
+    Verify(verify::VerifyArgs),
+    /// Compare two saves of the same format region by region
+    Diff(diff::DiffArgs),
+    /// Explain why a file is not recognized as a save, and what to do about it
+    Doctor(doctor::DoctorArgs),
+    /// Write each region of a save to its own file
+    Split(split::SplitArgs),
+    /// Reassemble a save from the files written by split
+    Join(split::JoinArgs),
+    /// List or reinstate the backups taken when an output was overwritten
+    Restore(restore::RestoreArgs),
+    /// Convert saves as they are copied into a directory
+    Watch(watch::WatchArgs),
+}
+
+/// Leftovers location shared by the commands that read or write them
+#[derive(Args, Clone, Default)]
+pub struct LeftoversArgs {
+    /// Keep leftovers in this directory keyed by save SHA1 instead of a sidecar next to the PC-ready file
+    #[arg(long, value_name = "DIR")]
+    pub leftovers_dir: Option<String>,
+
+    /// Keep leftovers in this file instead of a sidecar next to the PC-ready file
+    #[arg(long = "leftovers", value_name = "FILE", conflicts_with = "leftovers_dir")]
+    pub leftovers_file: Option<String>,
+
+    /// Keep leftovers in a vault shared by every conversion, found again however the save is moved, renamed or edited
+    /// (DIR defaults to $XDG_DATA_HOME/xv2-converter/leftovers)
+    #[arg(long, value_name = "DIR", num_args = 0..=1, conflicts_with_all = ["leftovers_dir", "leftovers_file"])]
+    pub vault: Option<Option<String>>,
+}
+
+pub fn run() -> Result<()> {
+    let cli = Cli::parse();
+    match cli.command {
+        Some(Command::Convert(args)) => convert::run(args),
+        Some(Command::Info(args)) => info::run(args),
+        Some(Command::Verify(args)) => verify::run(args),
+        Some(Command::Diff(args)) => diff::run(args),
+        Some(Command::Doctor(args)) => doctor::run(args),
+        Some(Command::Split(args)) => split::run(args),
+        Some(Command::Join(args)) => split::join(args),
+        Some(Command::Restore(args)) => restore::run(args),
+        Some(Command::Watch(args)) => watch::run(args),
+        None if cli.input_files.is_empty() => {
+            eprintln!("No input file given; see --help.");
+            std::process::exit(exit::USAGE);
+        }
+        None => convert::run(convert::ConvertArgs { input_files: cli.input_files, options: cli.convert }),
+    }
+}
+
+/// Why an input was not processed
+pub enum Failure {
+    /// The input file does not exist
+    NotFound(String),
+    /// The input was read but is not a save this build recognizes; `code` says which check it failed
+    Unrecognized { error: xv2::ConversionError, code: i32 },
+    /// The input was recognized but the tool declined to process it
+    Refused(String),
+    Error(anyhow::Error),
+}
+
+impl<E> From<E> for Failure
+where
+    anyhow::Error: From<E>,
+{
+    fn from(e: E) -> Self {
+        Failure::Error(anyhow::Error::from(e))
+    }
+}
+
+impl Failure {
+    /// Prints the failure and exits for missing or refused inputs; errors are handed back for `main` to report
+    fn exit(self) -> anyhow::Error {
+        match self {
+            Failure::Error(e) => e,
+            other => {
+                eprintln!("{}", other.message());
+                std::process::exit(other.exit_code());
+            }
+        }
+    }
+
+    fn message(&self) -> String {
+        match self {
+            Failure::NotFound(msg) | Failure::Refused(msg) => msg.clone(),
+            Failure::Unrecognized { error, .. } => format!("{} Run `doctor` on it for details.", error),
+            Failure::Error(e) => format!("Error: {:#}", e),
+        }
+    }
+
+    fn exit_code(&self) -> i32 {
+        match self {
+            Failure::NotFound(_) => exit::NOT_FOUND,
+            Failure::Unrecognized { code, .. } => *code,
+            Failure::Refused(_) => exit::REFUSED,
+            Failure::Error(e) => exit::for_error(e),
+        }
+    }
+}
+
+/// Path meaning stdin as an input and stdout as an output
+pub const STDIO: &str = "-";
+
+/// Reads `path`, or stdin for `-`
+fn read_input(path: &str) -> std::result::Result<Vec<u8>, Failure> {
+    if path == STDIO {
+        let mut data = Vec::new();
+        std::io::stdin().lock().read_to_end(&mut data)
+            .context("Failed to read input from stdin")?;
+        return Ok(data);
+    }
+    if !Path::new(path).exists() {
+        return Err(Failure::NotFound(format!("Input not found: {}", path)));
+    }
+    Ok(xv2::read_file_bytes(path)
+        .with_context(|| format!("Failed to read input file: {}", path))?)
+}
+
+/// Reads `path` (stdin for `-`) and detects its format
+fn load_save(path: &str) -> std::result::Result<(Vec<u8>, xv2::Detection), Failure> {
+    let data = read_input(path)?;
+
+    // Pick the format and game-version layout from the file size plus '#SAV' / marker positions
+    match xv2::detect(&data) {
+        Ok(detected) => Ok((data, detected)),
+        Err(error) => {
+            let code = exit::for_unrecognized(&data, &error);
+            Err(Failure::Unrecognized { error, code })
+        }
+    }
+}
+
+/// `load_save` that exits with a message if the input is missing or not recognized
+fn read_save(path: &str) -> Result<(Vec<u8>, xv2::Detection)> {
+    load_save(path).map_err(Failure::exit)
+}
+
+/// One-line MD5 header status of a PS4 save; unverified, since the header format is inferred
+fn report_md5(label: &str, ps4: &[u8]) -> Result<String> {
+    let status = xv2::check_md5_header(ps4)?;
+    if status.matches {
+        Ok(format!("{} MD5 header (unverified): OK ({})", label, xv2::to_hex(&status.computed)))
+    } else {
+        Ok(format!("{} MD5 header (unverified): MISMATCH (stored {}, computed {})", label,
+                   xv2::to_hex(&status.stored), xv2::to_hex(&status.computed)))
+    }
+}
+
+/// The `--leftovers` file, `--leftovers-dir` or `--vault` store if given, otherwise the sidecar of the PC-ready save.
+/// `None` when the PC-ready save is piped (`-`) and no location was given.
+fn leftovers_store(args: &LeftoversArgs, pcready_path: &str) -> Option<Box<dyn xv2::LeftoversStore>> {
+    if let Some(dir) = &args.vault {
+        let root = dir.as_ref().map(PathBuf::from).or_else(xv2::VaultStore::default_root)?;
+        return Some(Box::new(xv2::VaultStore::new(root)));
+    }
+    match (&args.leftovers_file, &args.leftovers_dir) {
+        (Some(file), _) => Some(Box::new(xv2::SidecarStore::new(file))),
+        (None, Some(dir)) => Some(Box::new(xv2::ContentAddressedStore::new(dir))),
+        (None, None) if pcready_path == STDIO => None,
+        (None, None) => Some(Box::new(xv2::SidecarStore::for_pcready(pcready_path))),
+    }
+}
+
+/// `leftovers_store` for reading leftovers back: without an explicit location, leftovers missing from the sidecar
+/// are also looked up in the default vault if there is one
+fn lookup_store(args: &LeftoversArgs, pcready_path: &str) -> Option<Box<dyn xv2::LeftoversStore>> {
+    let explicit = args.leftovers_file.is_some() || args.leftovers_dir.is_some() || args.vault.is_some();
+    let vault = xv2::VaultStore::default_root().filter(|root| root.is_dir()).map(xv2::VaultStore::new);
+    match (leftovers_store(args, pcready_path), vault) {
+        (store, _) if explicit => store,
+        (Some(sidecar), Some(vault)) => Some(Box::new(SidecarOrVault { sidecar, vault })),
+        (None, Some(vault)) => Some(Box::new(vault)),
+        (store, None) => store,
+    }
+}
+
+/// Sidecar of a PC-ready save, falling back to the default vault for leftovers it does not have
+struct SidecarOrVault {
+    sidecar: Box<dyn xv2::LeftoversStore>,
+    vault: xv2::VaultStore,
+}
+
+impl SidecarOrVault {
+    fn in_vault(&self, key: &xv2::LeftoversKey) -> bool {
+        matches!(self.sidecar.load(key), Ok(None)) && matches!(self.vault.load(key), Ok(Some(_)))
+    }
+}
+
+impl LeftoversStore for SidecarOrVault {
+    fn store(&mut self, key: &xv2::LeftoversKey, leftovers: &[u8]) -> std::io::Result<()> {
+        self.sidecar.store(key, leftovers)
+    }
+
+    fn load(&self, key: &xv2::LeftoversKey) -> std::io::Result<Option<Vec<u8>>> {
+        match self.sidecar.load(key)? {
+            Some(leftovers) => Ok(Some(leftovers)),
+            None => self.vault.load(key),
+        }
+    }
+
+    fn describe(&self, key: &xv2::LeftoversKey) -> String {
+        match self.in_vault(key) {
+            true => self.vault.describe(key),
+            false => self.sidecar.describe(key),
+        }
+    }
+}
+
+/// Provenance status of a PC-ready save, with what its record says about where it came from
+fn describe_provenance(check: &xv2::ProvenanceCheck) -> String {
+    match &check.record {
+        Some(record) => format!("{} (packed by {} from PS4 save {})", check.status.name(), record.tool_version, record.ps4_sha1),
+        None => check.status.name().to_string(),
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path).file_name()
+        .unwrap_or(std::ffi::OsStr::new(""))
+        .to_string_lossy()
+        .to_string()
+}
+
+fn print_ranges(diffs: &[xv2::DiffRange]) {
+    for d in diffs {
+        println!("  0x{:06X}..0x{:06X} (0x{:X} bytes) {}", d.start, d.end, d.end - d.start, d.region);
+    }
+}
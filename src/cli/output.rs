@@ -0,0 +1,158 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::{Failure, STDIO};
+
+/// Where a conversion writes its result
+#[derive(Args, Clone, Default)]
+pub struct OutputArgs {
+    /// Write the converted save to this file (`-` for stdout)
+    #[arg(short, long, value_name = "FILE", conflicts_with_all = ["output_dir", "name_template"])]
+    pub output: Option<String>,
+
+    /// Write the converted save into this directory (default: the input's directory)
+    #[arg(long, value_name = "DIR")]
+    pub output_dir: Option<String>,
+
+    /// File name of the converted save; placeholders: {stem} (input name without extension, "stdin" for `-`),
+    /// {direction} (ps4topc or pctops4), {sha1_8} (first 8 hex digits of the output SHA1),
+    /// {ext} (sav for PC-ready output, DAT for PS4 output)
+    #[arg(long, value_name = "TEMPLATE")]
+    pub name_template: Option<String>,
+
+    /// Refuse to overwrite an existing output instead of backing it up
+    #[arg(long, conflicts_with = "no_backup")]
+    pub no_clobber: bool,
+
+    /// Replace an existing output without keeping a backup of it
+    #[arg(long)]
+    pub no_backup: bool,
+}
+
+impl OutputArgs {
+    /// Output path for converting `input_path` into `output` of format `to`.
+    /// Without any option this is EditorReady.sav / SDATA000.DAT next to the input, or stdout for stdin input.
+    pub fn resolve(&self, input_path: &str, to: xv2::SaveFormat, output: &[u8]) -> Result<String> {
+        if let Some(path) = &self.output {
+            return Ok(path.clone());
+        }
+        if self.writes_stdout(input_path) {
+            return Ok(STDIO.to_string());
+        }
+
+        let dir = match &self.output_dir {
+            Some(dir) => PathBuf::from(dir),
+            None => Path::new(input_path).parent().map(Path::to_path_buf).unwrap_or_default(),
+        };
+        let name = match &self.name_template {
+            Some(template) => expand_template(template, input_path, to, output)?,
+            None => default_name(to).to_string(),
+        };
+        Ok(dir.join(name).to_string_lossy().to_string())
+    }
+
+    /// Clears the way for writing `out_path` (and its leftovers `sidecar`, if one will be written):
+    /// refuses if `--no-clobber` and the output exists, otherwise backs existing files up with one stamp (unless `--no-backup`)
+    pub fn prepare(&self, out_path: &str, sidecar: Option<&Path>) -> std::result::Result<Vec<xv2::Backup>, Failure> {
+        let mut paths = Vec::new();
+        if out_path != STDIO {
+            self.check_clobber(out_path)?;
+            ensure_parent_dir(out_path)?;
+            paths.push(Path::new(out_path));
+        }
+        paths.extend(sidecar);
+        if self.no_backup {
+            return Ok(Vec::new());
+        }
+        let stamp = xv2::backup_stamp(&paths);
+        let mut backups = Vec::new();
+        for path in paths {
+            let backup = xv2::backup_existing(path, &stamp)
+                .with_context(|| format!("Failed to back up {}", path.display()))?;
+            backups.extend(backup);
+        }
+        Ok(backups)
+    }
+
+    /// Whether the converted save of `input_path` goes to stdout
+    pub fn writes_stdout(&self, input_path: &str) -> bool {
+        match &self.output {
+            Some(path) => path == STDIO,
+            None => input_path == STDIO && self.output_dir.is_none(),
+        }
+    }
+
+    /// Refuses if `--no-clobber` and `out_path` exists
+    pub fn check_clobber(&self, out_path: &str) -> std::result::Result<(), Failure> {
+        if self.no_clobber && Path::new(out_path).exists() {
+            return Err(Failure::Refused(format!("Refusing to overwrite existing file: {}", out_path)));
+        }
+        Ok(())
+    }
+
+    /// How the final write treats the output; `prepare` has already backed it up
+    pub fn overwrite(&self) -> xv2::Overwrite {
+        if self.no_clobber { xv2::Overwrite::Refuse } else { xv2::Overwrite::Replace }
+    }
+}
+
+pub fn report_backups(backups: &[xv2::Backup]) {
+    for backup in backups {
+        println!("BACKUP → {}", backup.path.display());
+    }
+}
+
+/// Creates the directory `path` goes into, if any
+pub fn ensure_parent_dir(path: &str) -> Result<()> {
+    if let Some(dir) = Path::new(path).parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create directory: {}", dir.display()))?;
+    }
+    Ok(())
+}
+
+/// File name the game or editor expects for a save of format `to`
+pub fn default_name(to: xv2::SaveFormat) -> &'static str {
+    match to {
+        xv2::SaveFormat::PcReady => "EditorReady.sav",
+        xv2::SaveFormat::Ps4 => "SDATA000.DAT",
+    }
+}
+
+fn expand_template(template: &str, input_path: &str, to: xv2::SaveFormat, output: &[u8]) -> Result<String> {
+    let stem = match input_path {
+        STDIO => "stdin".to_string(),
+        path => Path::new(path).file_stem().map(|s| s.to_string_lossy().to_string()).unwrap_or_default(),
+    };
+    let (direction, ext) = match to {
+        xv2::SaveFormat::PcReady => ("ps4topc", "sav"),
+        xv2::SaveFormat::Ps4 => ("pctops4", "DAT"),
+    };
+
+    let mut name = String::new();
+    let mut rest = template;
+    while let Some(open) = rest.find('{') {
+        name.push_str(&rest[..open]);
+        let Some(close) = rest[open..].find('}') else {
+            bail!("Unterminated placeholder in name template: {}", template);
+        };
+        match &rest[open + 1..open + close] {
+            "stem" => name.push_str(&stem),
+            "direction" => name.push_str(direction),
+            "sha1_8" => name.push_str(&xv2::sha1_hex(output)[..8]),
+            "ext" => name.push_str(ext),
+            other => bail!("Unknown placeholder {{{}}} in name template", other),
+        }
+        rest = &rest[open + close + 1..];
+    }
+    name.push_str(rest);
+
+    if name.is_empty() || name.contains(['/', '\\']) {
+        bail!("Name template must produce a plain file name, got '{}'", name);
+    }
+    Ok(name)
+}
@@ -0,0 +1,88 @@
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::exit;
+use super::output::report_backups;
+
+#[derive(Args)]
+pub struct RestoreArgs {
+    /// Output file whose backups to list or reinstate
+    pub file: String,
+
+    /// Reinstate the most recent backup
+    #[arg(long, conflicts_with = "backup")]
+    pub latest: bool,
+
+    /// Reinstate the backup with this stamp (as listed)
+    #[arg(long, value_name = "STAMP")]
+    pub backup: Option<String>,
+}
+
+/// Lists the backups of a file, or reinstates one (together with its leftovers sidecar, if it had one).
+/// The current file is backed up first, so a restore can itself be undone.
+pub fn run(args: RestoreArgs) -> Result<()> {
+    let target = Path::new(&args.file);
+    let sidecar = xv2::SidecarStore::for_pcready(target).path().to_path_buf();
+    let backups = xv2::list_backups(target)
+        .with_context(|| format!("Failed to list backups of {}", args.file))?;
+
+    let chosen = match (&args.backup, args.latest) {
+        (Some(stamp), _) => backups.iter().find(|b| &b.stamp == stamp),
+        (None, true) => backups.first(),
+        (None, false) => {
+            list(&args.file, &backups, &sidecar);
+            return Ok(());
+        }
+    };
+    let Some(chosen) = chosen else {
+        eprintln!("No matching backup of {}; run without --latest/--backup to list them.", args.file);
+        std::process::exit(exit::NOT_FOUND);
+    };
+
+    let data = xv2::read_file_bytes(&chosen.path)
+        .with_context(|| format!("Failed to read backup: {}", chosen.path.display()))?;
+    let sidecar_backup = xv2::backup_path(&sidecar, &chosen.stamp);
+    let sidecar_data = match sidecar_backup.exists() {
+        true => Some(xv2::read_file_bytes(&sidecar_backup)
+            .with_context(|| format!("Failed to read backup: {}", sidecar_backup.display()))?),
+        false => None,
+    };
+
+    let stamp = xv2::backup_stamp(&[target, &sidecar]);
+    let mut current = Vec::new();
+    for path in [target, sidecar.as_path()] {
+        current.extend(xv2::backup_existing(path, &stamp)
+            .with_context(|| format!("Failed to back up {}", path.display()))?);
+    }
+    report_backups(&current);
+
+    xv2::write_atomic(target, &data)
+        .with_context(|| format!("Failed to write {}", args.file))?;
+    println!("RESTORED {} ← {}", args.file, chosen.path.display());
+    if let Some(lf) = sidecar_data {
+        xv2::write_atomic(&sidecar, &lf)
+            .with_context(|| format!("Failed to write {}", sidecar.display()))?;
+        println!("RESTORED {} ← {}", sidecar.display(), sidecar_backup.display());
+    }
+    println!("SHA1: {}", xv2::sha1_hex(&data));
+    Ok(())
+}
+
+fn list(file: &str, backups: &[xv2::Backup], sidecar: &Path) {
+    if backups.is_empty() {
+        println!("No backups of {}", file);
+        return;
+    }
+    println!("Backups of {} (newest first):", file);
+    for backup in backups {
+        let (size, sha1) = match xv2::read_file_bytes(&backup.path) {
+            Ok(data) => (format!("0x{:X}", data.len()), xv2::sha1_hex(&data)[..8].to_string()),
+            Err(_) => ("?".to_string(), "unreadable".to_string()),
+        };
+        let with_sidecar = if xv2::backup_path(sidecar, &backup.stamp).exists() { " + leftovers" } else { "" };
+        println!("  {}  {} bytes  SHA1 {}{}", backup.stamp, size, sha1, with_sidecar);
+    }
+}
@@ -0,0 +1,89 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::output::report_backups;
+use super::{exit, file_name, read_save};
+
+#[derive(Args)]
+pub struct SplitArgs {
+    /// Save file to split
+    pub input_file: String,
+
+    /// Directory receiving the region files (default: <input>.parts next to the input)
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<String>,
+}
+
+#[derive(Args)]
+pub struct JoinArgs {
+    /// Directory holding the region files written by split
+    pub parts_dir: String,
+
+    /// Path of the reassembled save
+    pub output_file: String,
+}
+
+/// Writes each region of the save to `NN-<region>.bin`, in file order
+pub fn run(args: SplitArgs) -> Result<()> {
+    let (data, detected) = read_save(&args.input_file)?;
+    let out_dir = args.out_dir.unwrap_or_else(|| format!("{}.parts", args.input_file));
+    fs::create_dir_all(&out_dir)
+        .with_context(|| format!("Failed to create directory: {}", out_dir))?;
+
+    println!("Splitting {} save ({} layout) into {}", detected.format.name(), detected.layout.name, out_dir);
+    for (i, span) in spans(&data, detected).iter().enumerate() {
+        let name = format!("{:02}-{}.bin", i, span.region.to_string().replace(' ', "-").to_lowercase());
+        let path = Path::new(&out_dir).join(&name).to_string_lossy().to_string();
+        xv2::write_output_file(&path, &data[span.start..span.end])?;
+        println!("  0x{:06X}..0x{:06X} (0x{:X} bytes) → {}", span.start, span.end, span.end - span.start, name);
+    }
+    Ok(())
+}
+
+/// Concatenates the `.bin` files of a split directory in name order; refuses if the result is not a known save
+pub fn join(args: JoinArgs) -> Result<()> {
+    let mut parts: Vec<PathBuf> = fs::read_dir(&args.parts_dir)
+        .with_context(|| format!("Failed to read directory: {}", args.parts_dir))?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|p| p.extension().is_some_and(|ext| ext == "bin"))
+        .collect();
+    parts.sort();
+
+    let mut data = Vec::new();
+    for part in &parts {
+        data.extend(xv2::read_file_bytes(part)
+            .with_context(|| format!("Failed to read part: {}", part.display()))?);
+    }
+
+    let detected = match xv2::detect(&data) {
+        Ok(d) => d,
+        Err(e) => {
+            eprintln!("Refusing to join {} part(s): {}", parts.len(), e);
+            std::process::exit(exit::for_unrecognized(&data, &e));
+        }
+    };
+
+    let backup = xv2::write_output_file_with(&args.output_file, &data, xv2::Overwrite::Backup)?;
+    report_backups(backup.as_slice());
+    println!("Joined {} part(s) into {} save ({} layout) → {}",
+             parts.len(), detected.format.name(), detected.layout.name, file_name(&args.output_file));
+    println!("Output SHA1: {}", xv2::sha1_hex(&data));
+    Ok(())
+}
+
+/// Consecutive byte ranges of `data` that fall in the same region
+fn spans(data: &[u8], detected: xv2::Detection) -> Vec<xv2::DiffRange> {
+    let mut spans: Vec<xv2::DiffRange> = Vec::new();
+    for i in 0..data.len() {
+        let region = xv2::region_at(i, detected.format, detected.layout, data.len());
+        match spans.last_mut() {
+            Some(last) if last.region == region => last.end = i + 1,
+            _ => spans.push(xv2::DiffRange { region, start: i, end: i + 1 }),
+        }
+    }
+    spans
+}
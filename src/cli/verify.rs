@@ -0,0 +1,64 @@
+use anyhow::{Context, Result};
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::{describe_provenance, exit, lookup_store, print_ranges, read_save, LeftoversArgs};
+
+#[derive(Args)]
+pub struct VerifyArgs {
+    /// Save file to round-trip
+    pub input_file: String,
+
+    #[command(flatten)]
+    pub leftovers: LeftoversArgs,
+
+    /// Accept a round trip whose only difference is the provenance record, which packing writes anew
+    #[arg(long)]
+    pub ignore_provenance: bool,
+}
+
+/// Round-trips the save in memory and prints the report; exits non-zero if it was not byte-exact (unless
+/// `--ignore-provenance` and only the provenance record differs)
+pub fn run(args: VerifyArgs) -> Result<()> {
+    let (data, _) = read_save(&args.input_file)?;
+
+    // PC-ready input needs the leftovers kept from its original conversion
+    let key = xv2::leftovers_key(&data);
+    let leftovers = match lookup_store(&args.leftovers, &args.input_file) {
+        Some(store) => store.load(&key)
+            .with_context(|| format!("Failed to read leftovers: {}", store.describe(&key)))?,
+        None => None,
+    };
+
+    let report = xv2::verify_round_trip(&data, leftovers.as_deref())?;
+    let trip = match report.format {
+        xv2::SaveFormat::Ps4 => "PS4→PC→PS4",
+        xv2::SaveFormat::PcReady => "PC→PS4→PC",
+    };
+    println!("Round trip {} ({} layout)", trip, report.layout.name);
+    for warning in &report.warnings {
+        println!("{}", warning);
+    }
+    println!("Input        SHA1: {}", report.input_sha1);
+    println!("Intermediate SHA1: {}", report.intermediate_sha1);
+    println!("Result       SHA1: {}", report.result_sha1);
+    if let Some(check) = &report.provenance {
+        println!("Provenance:        {}", describe_provenance(check));
+    }
+
+    if report.is_exact() {
+        println!("Round trip is byte-exact.");
+        Ok(())
+    } else if report.is_exact_but_provenance() {
+        println!("Round trip is byte-exact apart from the provenance record, which packing writes anew.");
+        if !args.ignore_provenance {
+            print_ranges(&report.diffs);
+            std::process::exit(exit::MISMATCH);
+        }
+        Ok(())
+    } else {
+        println!("Round trip differs in {} range(s):", report.diffs.len());
+        print_ranges(&report.diffs);
+        std::process::exit(exit::MISMATCH);
+    }
+}
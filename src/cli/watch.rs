@@ -0,0 +1,289 @@
+use std::collections::HashMap;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use xv2_converter_lib as xv2;
+
+use super::batch;
+use super::convert::{convert_file, ConvertOptions, Direction, MissingLeftovers};
+use super::output::OutputArgs;
+use super::{file_name, LeftoversArgs};
+
+#[derive(Args)]
+pub struct WatchArgs {
+    /// Directory to watch for new or changed saves (not its subdirectories)
+    pub dir: String,
+
+    /// Where converted saves and their leftovers go (default: <dir>/converted)
+    #[arg(long, value_name = "DIR")]
+    pub out_dir: Option<String>,
+
+    /// File name of the converted save, as for convert --name-template; the default keeps saves with different names apart
+    #[arg(long, value_name = "TEMPLATE", default_value = DEFAULT_NAME_TEMPLATE)]
+    pub name_template: String,
+
+    #[command(flatten)]
+    pub leftovers: LeftoversArgs,
+
+    /// Seconds a file must stay unchanged before it is converted
+    #[arg(long, value_name = "SECS", default_value_t = 2.0)]
+    pub settle: f64,
+}
+
+/// Output name unless --name-template says otherwise, e.g. `SDATA000-ps4topc.sav`
+const DEFAULT_NAME_TEMPLATE: &str = "{stem}-{direction}.{ext}";
+
+/// A file seen changing, waiting to stay unchanged for the settle time
+struct Pending {
+    since: Instant,
+    snapshot: Option<(u64, SystemTime)>,
+}
+
+/// Converts saves as they land in `dir`; runs until interrupted
+pub fn run(args: WatchArgs) -> Result<()> {
+    let dir = PathBuf::from(&args.dir);
+    if !dir.is_dir() {
+        bail!("Not a directory: {}", args.dir);
+    }
+    let out_dir = match &args.out_dir {
+        Some(out) => PathBuf::from(out),
+        None => dir.join("converted"),
+    };
+    fs::create_dir_all(&out_dir)
+        .with_context(|| format!("Failed to create output directory: {}", out_dir.display()))?;
+    let settle = Duration::try_from_secs_f64(args.settle).context("Invalid --settle")?;
+
+    let options = ConvertOptions {
+        direction: Direction::Auto,
+        output: OutputArgs {
+            output_dir: Some(out_dir.to_string_lossy().to_string()),
+            name_template: Some(args.name_template.clone()),
+            ..Default::default()
+        },
+        leftovers: args.leftovers.clone(),
+        batch: Default::default(),
+        missing_leftovers: MissingLeftovers::Strict,
+        check_md5: false,
+        dry_run: false,
+        json: false,
+    };
+
+    let inotify = Inotify::watch(&dir)?;
+    log(&format!("Watching {} → {} (Ctrl+C to stop)", dir.display(), out_dir.display()));
+
+    let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+    // SHA1 of every file this watcher wrote or already converted, so neither is picked up again
+    let mut seen: HashMap<PathBuf, String> = HashMap::new();
+    loop {
+        for name in inotify.read_events(Duration::from_millis(250))? {
+            if is_temporary(&name) {
+                continue;
+            }
+            let path = dir.join(name);
+            pending.insert(path.clone(), Pending { since: Instant::now(), snapshot: snapshot(&path) });
+        }
+
+        let settled: Vec<PathBuf> = pending.iter()
+            .filter(|(_, p)| p.since.elapsed() >= settle)
+            .map(|(path, _)| path.clone())
+            .collect();
+        for path in settled {
+            let now = snapshot(&path);
+            let entry = pending.get_mut(&path).expect("settled paths are pending");
+            if now != entry.snapshot {
+                // Still being written
+                *entry = Pending { since: Instant::now(), snapshot: now };
+                continue;
+            }
+            pending.remove(&path);
+            match now {
+                Some((len, _)) if batch::is_candidate(&path, len) => convert(&path, &options, &mut seen),
+                // Other files (notes, leftovers sidecars) are ignored quietly; save-named ones are worth a line
+                Some((len, _)) if has_save_extension(&path) => {
+                    log(&format!("{}: skipped: size 0x{:X} matches no known layout", file_name(&path.to_string_lossy()), len));
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+/// Converts one settled file and logs the outcome, unless it is unchanged since this watcher last wrote or read it
+fn convert(path: &Path, options: &ConvertOptions, seen: &mut HashMap<PathBuf, String>) {
+    let Ok(data) = xv2::read_file_bytes(path) else { return };
+    let sha1 = xv2::sha1_hex(&data);
+    if seen.get(&canonical(path)) == Some(&sha1) {
+        return;
+    }
+    seen.insert(canonical(path), sha1);
+
+    // convert_file takes the path as a string, and a lossy one would name another file
+    let Some(input) = path.to_str().map(str::to_string) else {
+        log(&format!("{}: skipped: the file name is not valid UTF-8", file_name(&path.to_string_lossy())));
+        return;
+    };
+    let mut steps = Vec::new();
+    match convert_file(&input, options, None, &mut steps) {
+        Ok(c) => {
+            let leftovers = match &c.leftovers {
+                Some((location, len)) => format!(", leftovers 0x{:X} bytes ({})", len, location),
+                None => String::new(),
+            };
+            let backup = match c.backups.first() {
+                Some(b) => format!(", previous output kept as {}", file_name(&b.path.to_string_lossy())),
+                None => String::new(),
+            };
+            log(&format!("{}: {} → {}{}{}", file_name(&input), c.direction(), c.out_path, leftovers, backup));
+            seen.insert(canonical(Path::new(&c.out_path)), c.output_sha1);
+        }
+        Err(f) => log(&format!("{}: not converted: {}", file_name(&input), f.message())),
+    }
+}
+
+/// Backups and in-progress atomic writes, never worth converting
+fn is_temporary(name: &OsStr) -> bool {
+    let name = name.to_string_lossy();
+    name.contains(".bak-") || name.contains(".tmp-") || name.starts_with('.')
+}
+
+fn has_save_extension(path: &Path) -> bool {
+    path.extension().is_some_and(|ext| ext.eq_ignore_ascii_case("dat") || ext.eq_ignore_ascii_case("sav"))
+}
+
+fn snapshot(path: &Path) -> Option<(u64, SystemTime)> {
+    let meta = fs::metadata(path).ok().filter(|m| m.is_file())?;
+    Some((meta.len(), meta.modified().ok()?))
+}
+
+/// Same key for a file whether it is reached through the watched directory or the output directory
+fn canonical(path: &Path) -> PathBuf {
+    fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Prints a line prefixed with the local time
+fn log(line: &str) {
+    println!("[{}] {}", local_time(), line);
+}
+
+#[cfg(unix)]
+fn local_time() -> String {
+    // SAFETY: time() and localtime_r() only write to the locals passed in
+    unsafe {
+        let now = libc::time(std::ptr::null_mut());
+        let mut tm: libc::tm = std::mem::zeroed();
+        if libc::localtime_r(&now, &mut tm).is_null() {
+            return "--:--:--".to_string();
+        }
+        format!("{:02}:{:02}:{:02}", tm.tm_hour, tm.tm_min, tm.tm_sec)
+    }
+}
+
+#[cfg(not(unix))]
+fn local_time() -> String {
+    let secs = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    format!("{:02}:{:02}:{:02} UTC", secs / 3600 % 24, secs / 60 % 60, secs % 60)
+}
+
+/// inotify watch on one directory, reporting the names of files created, written or moved in
+#[cfg(target_os = "linux")]
+struct Inotify {
+    fd: libc::c_int,
+}
+
+#[cfg(target_os = "linux")]
+impl Inotify {
+    fn watch(dir: &Path) -> Result<Self> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let c_dir = std::ffi::CString::new(dir.as_os_str().as_bytes()).context("Directory path contains a NUL byte")?;
+        // SAFETY: plain syscalls; the path is a valid NUL-terminated string
+        unsafe {
+            let fd = libc::inotify_init1(libc::IN_CLOEXEC);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error()).context("Failed to start inotify");
+            }
+            let inotify = Inotify { fd };
+            let mask = libc::IN_CREATE | libc::IN_MODIFY | libc::IN_CLOSE_WRITE | libc::IN_MOVED_TO;
+            if libc::inotify_add_watch(fd, c_dir.as_ptr(), mask) < 0 {
+                return Err(std::io::Error::last_os_error())
+                    .with_context(|| format!("Failed to watch {}", dir.display()));
+            }
+            Ok(inotify)
+        }
+    }
+
+    /// Names from the events that arrive within `timeout` (empty if none did)
+    fn read_events(&self, timeout: Duration) -> Result<Vec<OsString>> {
+        use std::os::unix::ffi::OsStrExt;
+
+        let mut poll = libc::pollfd { fd: self.fd, events: libc::POLLIN, revents: 0 };
+        // SAFETY: `poll` is a valid pollfd for the duration of the call
+        let ready = unsafe { libc::poll(&mut poll, 1, timeout.as_millis() as libc::c_int) };
+        if ready < 0 {
+            let err = std::io::Error::last_os_error();
+            if err.kind() == std::io::ErrorKind::Interrupted {
+                return Ok(Vec::new());
+            }
+            return Err(err).context("Failed to wait for inotify events");
+        }
+        if ready == 0 {
+            return Ok(Vec::new());
+        }
+
+        let mut buf = vec![0u8; 64 * 1024];
+        // SAFETY: reads at most `buf.len()` bytes into `buf`
+        let len = unsafe { libc::read(self.fd, buf.as_mut_ptr().cast(), buf.len()) };
+        if len < 0 {
+            return Err(std::io::Error::last_os_error()).context("Failed to read inotify events");
+        }
+
+        // Each event is a struct inotify_event followed by a NUL-padded name of `len` bytes
+        let header = std::mem::size_of::<libc::inotify_event>();
+        let mut names = Vec::new();
+        let mut pos = 0;
+        while pos + header <= len as usize {
+            // SAFETY: the kernel wrote a whole event header at `pos`; read_unaligned copes with the byte buffer
+            let event: libc::inotify_event = unsafe { std::ptr::read_unaligned(buf[pos..].as_ptr().cast()) };
+            let name_bytes = &buf[pos + header..pos + header + event.len as usize];
+            pos += header + event.len as usize;
+
+            if event.mask & libc::IN_Q_OVERFLOW != 0 {
+                log("Warning: too many events at once, some changes were missed");
+            }
+            if event.mask & libc::IN_IGNORED != 0 {
+                bail!("The watched directory was removed or unmounted");
+            }
+            let name = name_bytes.split(|&b| b == 0).next().unwrap_or_default();
+            if !name.is_empty() {
+                names.push(OsStr::from_bytes(name).to_os_string());
+            }
+        }
+        Ok(names)
+    }
+}
+
+#[cfg(target_os = "linux")]
+impl Drop for Inotify {
+    fn drop(&mut self) {
+        // SAFETY: `fd` is owned by this struct and closed only here
+        unsafe { libc::close(self.fd) };
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+struct Inotify;
+
+#[cfg(not(target_os = "linux"))]
+impl Inotify {
+    fn watch(_dir: &Path) -> Result<Self> {
+        bail!("watch needs Linux inotify and is not available on this platform");
+    }
+
+    fn read_events(&self, _timeout: Duration) -> Result<Vec<OsString>> {
+        Ok(Vec::new())
+    }
+}
@@ -1,4 +1,5 @@
 // ===== Fixed sizes (known save file sizes) =====
+// Sizes and offsets below describe DEFAULT_LAYOUT; conversions read them through a SaveLayout (see layout.rs)
 pub const PS4_SIZE: usize = 0x12A200;  // Total size of PS4 save file (includes 0x20 byte prefix)
 pub const EDITOR_SIZE: usize = 0x12A1F8;  // Total size of PC-ready save file
 
@@ -6,6 +7,7 @@ pub const EDITOR_SIZE: usize = 0x12A1F8;  // Total size of PC-ready save file
 pub const MD5_HEADER_SIZE: usize = 0x20; // Size of the MD5 header section (first 0x20 bytes to move)
 pub const SAV_HEADER_SIZE: usize = 0x80; // Size of the #SAV section (0x80 bytes with #SAV marker)
 pub const MARKER_OFFSET: usize = 0x08;   // Offset where the 8-byte format marker is located
+pub const MARKER_LEN: usize = 0x08;      // Length of the format marker
 
 // Hero Coliseum Data start in PS4 format (absolute offset in PS4 file where HCD data begins)
 // Used for aligning coliseum and mentor data sections
@@ -33,3 +35,4 @@ pub const FLAG_LEFTOVERS: u8 = 0x2B;   // '+' - extra data exists in sidecar fil
 
 // versions (last marker byte)
 pub const VER_V2: u8 = 0x31;
+pub const VER_V3: u8 = 0x32;  // same layout as v2, with the trimmed HCD tail stored in the fill gap (see embedded.rs)
@@ -1,63 +1,137 @@
-use anyhow::{Context, Result};
-use std::path::Path;
+use crate::detect::{self, SaveFormat};
+use crate::error::{ConversionError, Result};
+use crate::layout::SaveLayout;
+use crate::leftovers::{self, LeftoversHeader, LeftoversKey, LeftoversStore};
+use crate::provenance::{self, ProvenanceStatus};
+use crate::{constants, embedded, marker, utils};
+
+/// What unpacking does when the marker flags leftovers but they are missing or too short
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LeftoversPolicy {
+    /// Fail with `LeftoversMissing` / `LeftoversShort`
+    Strict,
+    /// Fill the missing HCD tail bytes with zeros and add a warning; the PS4 save is then not the original
+    #[default]
+    ZeroFill,
+}
+
+impl LeftoversPolicy {
+    pub fn name(&self) -> &'static str {
+        match self {
+            LeftoversPolicy::Strict => "strict",
+            LeftoversPolicy::ZeroFill => "zero_fill",
+        }
+    }
+
+    pub fn by_name(name: &str) -> Option<LeftoversPolicy> {
+        [LeftoversPolicy::Strict, LeftoversPolicy::ZeroFill].into_iter().find(|p| p.name() == name)
+    }
+}
+
+/// Result of a conversion: the converted save plus anything the caller has to persist or report.
+/// The library never touches the filesystem; writing `data` and `leftovers` is up to the caller.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionOutput {
+    /// Converted save data
+    pub data: Vec<u8>,
+    /// Packing: contents of the leftovers file to keep, i.e. the non-zero HCD tail bytes removed, behind a header
+    /// identifying the save (see `encode_leftovers`). Unpacking: the HCD tail bytes restored.
+    pub leftovers: Option<Vec<u8>>,
+    /// Non-fatal issues encountered during conversion
+    pub warnings: Vec<String>,
+    /// The HCD tail travels in the fill gap of the PC-ready save (marker v3): packing left nothing
+    /// in `leftovers` to keep, unpacking restored `leftovers` from the save itself
+    pub embedded: bool,
+}
+
+/// Sizes the packer derives from a layout; they are the same for every save of that layout
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PackGeometry {
+    /// Fill gap inserted between middle_segment and hcd_section (embedded leftovers or vault key, provenance record, zeros)
+    pub fill_len: usize,
+    /// Zero bytes appended after hcd_section when it ends up short
+    pub pad: usize,
+    /// Bytes trimmed from the end of hcd_section when it ends up long (the potential leftovers)
+    pub excess: usize,
+}
+
+pub fn pack_geometry(layout: &SaveLayout) -> Result<PackGeometry> {
+    layout.validate()?;
+
+    let lead = layout.marker_offset;
+    let middle_segment_len = layout.hcd_start_in_middle() - lead;
+    let hcd_section_len = layout.middle_len() - layout.hcd_start_in_middle();
 
-use crate::{constants, marker, utils, io};
+    let base_d_start = lead /*first_8_bytes*/ + constants::MARKER_LEN /*marker*/ + middle_segment_len; // where hcd_section would start with no fill
+    if base_d_start > layout.hcd_start_pc_ready {
+        return Err(ConversionError::InvalidLayout("fillLen negative, expected hcd_section start too early vs data"));
+    }
+    let fill_len = layout.hcd_start_pc_ready - base_d_start;
+
+    let prefix_len = layout.hcd_start_pc_ready + hcd_section_len;
+    let required_main_part_len = layout.editor_size - 1 - layout.sav_header_size - layout.md5_header_size;
+    Ok(PackGeometry {
+        fill_len,
+        pad: required_main_part_len.saturating_sub(prefix_len),
+        excess: prefix_len.saturating_sub(required_main_part_len),
+    })
+}
+
+pub fn ps4_to_pcready(data: &[u8]) -> Result<ConversionOutput> {
+    ps4_to_pcready_with_layout(data, detect::layout_for_ps4(data))
+}
 
-pub fn ps4_to_pcready(data: &[u8], input_path: &str, _dir: &str) -> Result<Vec<u8>> {
+pub fn ps4_to_pcready_with_layout(data: &[u8], layout: &SaveLayout) -> Result<ConversionOutput> {
     // PS4 save format: [0x20 bytes MD5 ][0x80 bytes with #SAV at 0x20][rest of data ending with Z]
     // PC-ready format: [processed data with marker system][Z_BYTE][SAV_HEADER][MD5_HEADER] where [SAV_HEADER] has #SAV and [MD5_HEADER] is the first 0x20 bytes
+    // (sizes given for the default layout; the actual ones come from `layout`)
+    layout.validate()?;
+
+    if data.len() != layout.ps4_size {
+        return Err(ConversionError::WrongSize { expected: layout.ps4_size, actual: data.len() });
+    }
+    for offset in layout.ps4_magic_offsets() {
+        if !marker::has_magic_at(data, offset) {
+            return Err(ConversionError::MissingMagic { offset });
+        }
+    }
+
+    let md5_size = layout.md5_header_size;
+    let sav_size = layout.sav_header_size;
+    let lead = layout.marker_offset; // bytes kept in front of the marker (8 in every known layout)
 
     // Extract the 0x20 MD5_HEADER and the original [SAV_HEADER] section (0x80 bytes with #SAV at 0x00 relative to SAV_HEADER)
-    let md5_header = &data[0..constants::MD5_HEADER_SIZE];  // First 0x20 bytes (to be moved to end)
-    let sav_header = &data[constants::MD5_HEADER_SIZE..constants::MD5_HEADER_SIZE + constants::SAV_HEADER_SIZE];  // Next 0x80 bytes with #SAV (to be moved to end)
+    let md5_header = &data[0..md5_size];  // First 0x20 bytes (to be moved to end)
+    let sav_header = &data[md5_size..md5_size + sav_size];  // Next 0x80 bytes with #SAV (to be moved to end)
     let z_byte = data[data.len() - 1];  // Last byte - preserved during conversion
-    let middle = &data[constants::MD5_HEADER_SIZE + constants::SAV_HEADER_SIZE..data.len() - 1];  // Middle part without md5_header/sav_header and z_byte
-    let hcd_start_in_middle = constants::HCD_START_PS4 - 0x80;
-
-    if middle.len() <= 8 {
-        return Err(anyhow::anyhow!("PS4 structure too small."));
-    }
+    let middle = &data[md5_size + sav_size..data.len() - 1];  // Middle part without md5_header/sav_header and z_byte
 
     // Extract data segments for the algorithm:
     // middle_segment = middle data between first_8_bytes and hcd_section
     // hcd_section = HCD data section (starts at HCD_START_PS4 in original PS4 format)
-    let first_8_bytes = &middle[0..8];  // first_8_bytes - first 8 bytes of middle data
+    let first_8_bytes = &middle[0..lead];  // first_8_bytes - first 8 bytes of middle data
 
     // Calculate where hcd_section starts in the middle data
-    let hcd_start_pos = hcd_start_in_middle; // Position of hcd_section within the middle slice
-    if hcd_start_pos < 8 {
-        return Err(anyhow::anyhow!("Bad Hcd1 start offset (middle_segment would be negative)."));
-    }
-    let middle_segment_len = hcd_start_pos - 8; // Length of middle_segment: middle[8 .. hcd_start_pos-1] (data between first_8_bytes and hcd_section)
-    let middle_segment = &middle[8..hcd_start_pos]; // middle_segment - middle segment between first_8_bytes and hcd_section
+    let hcd_start_pos = layout.hcd_start_in_middle(); // Position of hcd_section within the middle slice
+    let middle_segment_len = hcd_start_pos - lead; // Length of middle_segment: middle[8 .. hcd_start_pos-1] (data between first_8_bytes and hcd_section)
+    let middle_segment = &middle[lead..hcd_start_pos]; // middle_segment - middle segment between first_8_bytes and hcd_section
 
     let hcd_section_len = middle.len() - hcd_start_pos; // Length of hcd_section: middle[hcd_start_pos .. end] (HCD data)
-    if hcd_section_len == 0 {
-        return Err(anyhow::anyhow!("Bad Hcd1 start offset (hcd_section empty)."));
-    }
-
     let hcd_section = &middle[hcd_start_pos..]; // hcd_section - HCD data section
 
     // Calculate fill to place D at HCD_START_PC_READY in the final PC-ready output
-    let base_d_start = 8 /*first_8_bytes*/ + 8 /*marker*/ + middle_segment_len; // where hcd_section would start with no fill
-    if base_d_start > constants::HCD_START_PC_READY {
-        return Err(anyhow::anyhow!(
-            "fillLen negative (0x{:X}). ExpectedDStart too early vs data. Refusing.",
-            constants::HCD_START_PC_READY - base_d_start
-        ));
-    }
-    let fill_len = constants::HCD_START_PC_READY - base_d_start;
+    let fill_len = pack_geometry(layout)?.fill_len;
 
     // Build the processed data section: [first_8_bytes][marker(v2,T)][middle_segment][fill zeros][hcd_section]
     let marker = marker::make_marker(constants::VER_V2, constants::FLAG_NO_LEFTOVERS);
-    let prefix_len = 8 + 8 + middle_segment_len + fill_len + hcd_section_len;
+    let prefix_len = lead + constants::MARKER_LEN + middle_segment_len + fill_len + hcd_section_len;
     let mut prefix_data = vec![0u8; prefix_len];
 
     let mut p = 0;
-    prefix_data[p..p + 8].copy_from_slice(first_8_bytes);      // [first_8_bytes] - first 8 bytes of middle
-    p += 8;
-    prefix_data[p..p + 8].copy_from_slice(&marker); // [marker] - version marker
-    p += 8;
+    prefix_data[p..p + lead].copy_from_slice(first_8_bytes);      // [first_8_bytes] - first 8 bytes of middle
+    p += lead;
+    prefix_data[p..p + constants::MARKER_LEN].copy_from_slice(&marker); // [marker] - version marker
+    p += constants::MARKER_LEN;
     prefix_data[p..p + middle_segment_len].copy_from_slice(middle_segment);   // [middle_segment] - middle segment
     p += middle_segment_len;
     // fill zeros already default(0x00)
@@ -65,10 +139,10 @@ pub fn ps4_to_pcready(data: &[u8], input_path: &str, _dir: &str) -> Result<Vec<u
     prefix_data[p..p + hcd_section_len].copy_from_slice(hcd_section); // [hcd_section] - HCD data
 
     // Calculate if we need padding or if we have excess data
-    let required_main_part_len = constants::EDITOR_SIZE - 1 - constants::SAV_HEADER_SIZE - constants::MD5_HEADER_SIZE; // bytes before Z, A and PREFIX
+    let required_main_part_len = layout.editor_size - 1 - sav_size - md5_size; // bytes before Z, A and PREFIX
     let pad = required_main_part_len as isize - prefix_data.len() as isize;
 
-    let mut leftovers = false;
+    let mut leftovers: Option<Vec<u8>> = None;
 
     if pad > 0 {
         // Append zeros padding if we have space left
@@ -77,175 +151,259 @@ pub fn ps4_to_pcready(data: &[u8], input_path: &str, _dir: &str) -> Result<Vec<u
         // Trim excess from prefix_data if we have too much data
         let excess = (-pad) as usize;
         if excess > prefix_data.len() {
-            return Err(anyhow::anyhow!("Excess trim larger than prefix; refusing."));
+            return Err(ConversionError::InvalidLayout("excess trim larger than prefix"));
         }
 
         let removed = prefix_data[prefix_data.len() - excess..].to_vec();
         prefix_data.truncate(prefix_data.len() - excess);
 
-        let all_zero = utils::all_zero(&removed);
-        leftovers = !all_zero;
-
-        if leftovers {
-            // Write non-zero excess data to a sidecar file
-            let leftovers_path = format!("{}.leftovers.dec", input_path);
-            std::fs::write(&leftovers_path, &removed)
-                .with_context(|| format!("Failed to write leftovers file: {}", leftovers_path))?;
-            println!("LEFTOVERS → {} (0x{:X} bytes)",
-                     Path::new(&leftovers_path).file_name()
-                         .unwrap_or(std::ffi::OsStr::new(""))
-                         .to_string_lossy(),
-                     excess);
+        // Non-zero excess data has to be handed back to the caller so it can be restored on unpack
+        if !utils::all_zero(&removed) {
+            leftovers = Some(removed);
         }
     }
 
     if prefix_data.len() != required_main_part_len {
-        return Err(anyhow::anyhow!(
-            "Internal size mismatch: prefix 0x{:X} != required 0x{:X}.",
-            prefix_data.len(),
-            required_main_part_len
-        ));
+        return Err(ConversionError::SizeMismatch {
+            what: "prefix",
+            expected: required_main_part_len,
+            actual: prefix_data.len(),
+        });
     }
 
     // Assemble final PC-ready format: [prefix_data][z_byte][sav_header][md5_header]
-    let mut out_data = vec![0u8; constants::EDITOR_SIZE];
+    let mut out_data = vec![0u8; layout.editor_size];
     out_data[0..prefix_data.len()].copy_from_slice(&prefix_data); // [prefix_data] - processed data
     out_data[prefix_data.len()] = z_byte; // [z_byte] - last byte from original
-    out_data[prefix_data.len() + 1..prefix_data.len() + 1 + constants::SAV_HEADER_SIZE].copy_from_slice(sav_header); // [sav_header] - #SAV section
-    let pos = out_data.len() - constants::MD5_HEADER_SIZE;
+    out_data[prefix_data.len() + 1..prefix_data.len() + 1 + sav_size].copy_from_slice(sav_header); // [sav_header] - #SAV section
+    let pos = out_data.len() - md5_size;
     out_data[pos..].copy_from_slice(md5_header); // [md5_header] - original first 0x20 bytes
 
-    // Update marker leftovers flag if needed
-    if leftovers {
-        out_data[constants::MARKER_OFFSET + 5] = constants::FLAG_LEFTOVERS;
+    // Leftovers go into the fill gap when they fit (marker v3); otherwise the marker flags them for the caller to keep,
+    // and the gap names them for a leftovers vault. The end of the gap is kept for the provenance record.
+    let mut embedded = false;
+    let capacity = match provenance::record_range(layout) {
+        Some(_) => fill_len - provenance::RECORD_LEN,
+        None => fill_len,
+    };
+    if let Some(lf) = &leftovers {
+        let gap_start = layout.hcd_start_pc_ready - fill_len;
+        let record = match embedded::encode(lf, capacity) {
+            Some(record) => {
+                out_data[layout.marker_offset + 7] = constants::VER_V3;
+                embedded = true;
+                record
+            }
+            None => {
+                out_data[layout.marker_offset + 5] = constants::FLAG_LEFTOVERS;
+                embedded::encode_vault_key(&utils::sha1_hex(data))
+            }
+        };
+        out_data[gap_start..gap_start + record.len()].copy_from_slice(&record);
     }
+    if embedded {
+        leftovers = None;
+    }
+    provenance::write(&mut out_data, layout, utils::sha1(data));
+    // Kept leftovers carry a header so unpacking can tell whether they belong to the save it is given
+    let leftovers = leftovers.map(|lf| {
+        let header = LeftoversHeader {
+            ps4_sha1: utils::sha1_hex(data),
+            pcready_sha1: utils::sha1_hex(&out_data),
+            origin_sha1: utils::sha1_hex(md5_header),
+            hcd_offset: layout.ps4_size - 1 - lf.len(),
+            length: lf.len(),
+            crc32: utils::crc32(&lf),
+        };
+        leftovers::encode_leftovers(&header, &lf)
+    });
 
     // Final sanity check: [SAV_HEADER] must be at the right position and start with #SAV
-    if !marker::has_magic_at(&out_data, out_data.len() - constants::MD5_HEADER_SIZE - constants::SAV_HEADER_SIZE) {
-        return Err(anyhow::anyhow!("Packed v2 sanity failed: #SAV not found at start of [SAV_HEADER]."));
+    let sav_header_start = layout.pcready_sav_header_offset();
+    if !marker::has_magic_at(&out_data, sav_header_start) {
+        return Err(ConversionError::MissingMagic { offset: sav_header_start });
     }
 
-    if out_data.len() != constants::EDITOR_SIZE {
-        return Err(anyhow::anyhow!("Packed output size mismatch."));
+    if out_data.len() != layout.editor_size {
+        return Err(ConversionError::SizeMismatch {
+            what: "packed output",
+            expected: layout.editor_size,
+            actual: out_data.len(),
+        });
     }
 
-    Ok(out_data)
+    Ok(ConversionOutput { data: out_data, leftovers, warnings: Vec::new(), embedded })
 }
 
-pub fn convert_auto(data: &[u8], input_path: &str, dir: &str) -> Result<Vec<u8>> {
-    let (version, flag) = marker::try_read_marker(data)
-        .ok_or_else(|| anyhow::anyhow!("Marker not recognized at 0x08."))?;
+pub fn convert_auto(data: &[u8], leftovers: Option<&[u8]>, policy: LeftoversPolicy) -> Result<ConversionOutput> {
+    match detect::detect(data) {
+        Ok(d) if d.format == SaveFormat::Ps4 => ps4_to_pcready_with_layout(data, d.layout),
+        Ok(d) => convert_auto_with_layout(data, d.layout, leftovers, policy),
+        // Partial matches get the specific error of the direction they resemble
+        Err(_) if marker::has_dual_magic(data) => ps4_to_pcready(data),
+        Err(_) if marker::has_any_marker_at_08(data) => {
+            convert_auto_with_layout(data, detect::layout_for_pcready(data), leftovers, policy)
+        }
+        Err(e) => Err(e),
+    }
+}
+
+pub fn convert_auto_with_layout(data: &[u8], layout: &SaveLayout, leftovers: Option<&[u8]>, policy: LeftoversPolicy) -> Result<ConversionOutput> {
+    let (version, flag) = marker::try_read_marker_at(data, layout.marker_offset)
+        .ok_or(ConversionError::UnrecognizedMarker)?;
 
-    let looks_v2 = marker::looks_like_v2(data);
+    let looks_v2 = marker::looks_like_v2_in(data, layout);
 
-    // Only support v2 now
+    // v2, and v3 which shares its layout and keeps the HCD tail in the fill gap
     if version == constants::VER_V2 || looks_v2 {
         if !looks_v2 {
-            return Err(anyhow::anyhow!("Marker says v2 but layout sanity checks failed."));
+            return Err(ConversionError::MarkerLayoutMismatch);
         }
         let has_leftovers = flag == constants::FLAG_LEFTOVERS;
-        pcready_to_ps4(data, input_path, dir, has_leftovers)
+        pcready_to_ps4_with_layout(data, layout, has_leftovers, leftovers, policy)
     } else {
-        Err(anyhow::anyhow!("Only v2 format is supported now."))
+        Err(ConversionError::UnsupportedVersion(version))
     }
 }
 
-pub fn pcready_to_ps4(data: &[u8], input_path: &str, _dir: &str, has_leftovers_flag: bool) -> Result<Vec<u8>> {
-    if data.len() != constants::EDITOR_SIZE {
-        return Err(anyhow::anyhow!(
-            "v2 unpack expects editor size 0x{:X}, got 0x{:X}.",
-            constants::EDITOR_SIZE,
-            data.len()
-        ));
+pub fn pcready_to_ps4(data: &[u8], has_leftovers_flag: bool, leftovers: Option<&[u8]>, policy: LeftoversPolicy) -> Result<ConversionOutput> {
+    pcready_to_ps4_with_layout(data, detect::layout_for_pcready(data), has_leftovers_flag, leftovers, policy)
+}
+
+pub fn pcready_to_ps4_with_layout(
+    data: &[u8],
+    layout: &SaveLayout,
+    has_leftovers_flag: bool,
+    leftovers: Option<&[u8]>,
+    policy: LeftoversPolicy,
+) -> Result<ConversionOutput> {
+    layout.validate()?;
+
+    if data.len() != layout.editor_size {
+        return Err(ConversionError::WrongSize { expected: layout.editor_size, actual: data.len() });
     }
 
     // PC-ready format: [processed data][z_byte][sav_header][md5_header] where [sav_header] has #SAV and [md5_header] is the first 0x20 bytes
     // PS4 format: [md5_header][sav_header][middle][z_byte]
-    
+    let md5_size = layout.md5_header_size;
+    let sav_size = layout.sav_header_size;
+    let lead = layout.marker_offset; // bytes kept in front of the marker (8 in every known layout)
+
     // Extract [md5_header], [sav_header], and [z_byte] from the end of the PC-ready format
-    let md5_header_start = data.len() - constants::MD5_HEADER_SIZE;
-    let sav_header_start = data.len() - constants::MD5_HEADER_SIZE - constants::SAV_HEADER_SIZE;
+    let md5_header_start = data.len() - md5_size;
+    let sav_header_start = layout.pcready_sav_header_offset();
     let z_index = sav_header_start - 1;
 
     if !marker::has_magic_at(data, sav_header_start) {
-        return Err(anyhow::anyhow!("v2 unpack sanity failed: #SAV not found at start of [sav_header]."));
+        return Err(ConversionError::MissingMagic { offset: sav_header_start });
     }
 
-    let md5_header = &data[md5_header_start..md5_header_start + constants::MD5_HEADER_SIZE];  // [md5_header] - Last 0x20 bytes (was original [md5_header])
-    let sav_header = &data[sav_header_start..sav_header_start + constants::SAV_HEADER_SIZE];  // [sav_header] - Before the last 0x20 bytes (was original [sav_header] section)
+    let md5_header = &data[md5_header_start..md5_header_start + md5_size];  // [md5_header] - Last 0x20 bytes (was original [md5_header])
+    let sav_header = &data[sav_header_start..sav_header_start + sav_size];  // [sav_header] - Before the last 0x20 bytes (was original [sav_header] section)
     let z_byte = data[z_index];  // [z_byte] - Byte before [sav_header] section (was original z_byte)
 
     // Extract first_8_bytes (first 8 bytes of the processed data)
-    let first_8_bytes = &data[0..8];  // [first_8_bytes] - first 8 bytes of processed data
+    let first_8_bytes = &data[0..lead];  // [first_8_bytes] - first 8 bytes of processed data
 
-    let hcd_start_in_middle = constants::HCD_START_PS4 - 0x80;
-    if hcd_start_in_middle < 8 {
-        return Err(anyhow::anyhow!("Bad constants: middle_segment length negative."));
-    }
-    let middle_segment_len = hcd_start_in_middle - 8; // Length of middle_segment (minus 8 for [first_8_bytes])
+    let hcd_start_in_middle = layout.hcd_start_in_middle();
+    let middle_segment_len = hcd_start_in_middle - lead; // Length of middle_segment (minus 8 for [first_8_bytes])
 
-    let middle_segment_start_in_packed = 16; // After [first_8_bytes] + marker (8 + 8 = 16 bytes)
+    let middle_segment_start_in_packed = lead + constants::MARKER_LEN; // After [first_8_bytes] + marker (8 + 8 = 16 bytes)
     if middle_segment_start_in_packed + middle_segment_len > z_index {  // z_index is where the trailer starts
-        return Err(anyhow::anyhow!("v2 unpack: middle_segment out of range."));
+        return Err(ConversionError::InvalidLayout("middle_segment out of range"));
     }
 
     let middle_segment = &data[middle_segment_start_in_packed..middle_segment_start_in_packed + middle_segment_len];  // [middle_segment] section data
 
     // [hcd_section] present region is from expected hcd_section start to ZIndex (exclusive)
-    if constants::HCD_START_PC_READY > z_index {
-        return Err(anyhow::anyhow!("v2 unpack: hcd_section start beyond z_byte."));
-    }
-
-    let hcd_present_len = z_index - constants::HCD_START_PC_READY;
-    let hcd_present = &data[constants::HCD_START_PC_READY..constants::HCD_START_PC_READY + hcd_present_len];  // [hcd_section] section data
+    let hcd_present_len = z_index - layout.hcd_start_pc_ready;
+    let hcd_present = &data[layout.hcd_start_pc_ready..layout.hcd_start_pc_ready + hcd_present_len];  // [hcd_section] section data
 
     // Full [hcd_section] length in the middle part (original PS4 without md5_header/sav_header and z_byte): from calculated HCD1 start to end
     // The middle part length in original PS4 would be: PS4_SIZE - MD5_HEADER_SIZE - SAV_HEADER_SIZE - 1 (for z_byte)
-    let middle_total_len = constants::PS4_SIZE - constants::MD5_HEADER_SIZE - constants::SAV_HEADER_SIZE - 1;
+    let middle_total_len = layout.middle_len();
     let hcd_full_len = middle_total_len - hcd_start_in_middle; // From HCD start to end of middle
 
     if hcd_present_len > hcd_full_len {
-        return Err(anyhow::anyhow!(
-            "v2 unpack: hcd_section present is larger than hcd_section full (missing=0x{:X}). Refusing.",
-            hcd_present_len - hcd_full_len
-        ));
+        return Err(ConversionError::SizeMismatch {
+            what: "hcd_section present",
+            expected: hcd_full_len,
+            actual: hcd_present_len,
+        });
     }
     let missing = hcd_full_len - hcd_present_len;  // Missing bytes that need to be recovered
 
+    let mut warnings = Vec::new();
+    let mut restored = None;
     let mut hcd_tail = vec![0u8; missing];  // Buffer for missing [hcd_section] bytes
-    if missing > 0 {
-        let mut filled = false;
-
-        if has_leftovers_flag {
-            let leftovers_path = format!("{}.leftovers.dec", input_path);
-            if std::path::Path::new(&leftovers_path).exists() {
-                let lf = io::read_file_bytes(&leftovers_path)?;
+    let mut checked = None;
+    let mut embedded = marker::try_read_marker_at(data, layout.marker_offset)
+        .is_some_and(|(version, _)| version == constants::VER_V3);
+    if embedded {
+        // v3 keeps the missing bytes in the fill gap, in front of hcd_section
+        let fill_len = pack_geometry(layout)?.fill_len;
+        let gap = &data[layout.hcd_start_pc_ready - fill_len..layout.hcd_start_pc_ready];
+        match embedded::decode(gap, missing) {
+            Ok(tail) => {
+                hcd_tail = tail;
+                restored = Some(hcd_tail.clone());
+            }
+            Err(e) if policy == LeftoversPolicy::ZeroFill => {
+                warnings.push(format!("{} Filling the missing HCD tail with zeros.", e));
+                embedded = false;
+            }
+            Err(e) => return Err(e),
+        }
+    } else if missing > 0 && has_leftovers_flag {
+        match leftovers {
+            Some(file) => {
+                let lf = match leftovers::parse_leftovers(file)? {
+                    Some((header, lf)) => {
+                        check_leftovers(&header, data, layout, missing)?;
+                        checked = Some(header);
+                        lf
+                    }
+                    None => {
+                        // Extra bytes would be dropped unseen, so a longer legacy sidecar is not for this save
+                        if file.len() > missing {
+                            return Err(ConversionError::LeftoversMismatch(format!(
+                                "legacy sidecar holds 0x{:X} bytes but this save is missing only 0x{:X}", file.len(), missing
+                            )));
+                        }
+                        warnings.push("leftovers have no header (legacy sidecar), so they could not be checked against this save.".to_string());
+                        file
+                    }
+                };
                 let take = std::cmp::min(missing, lf.len());
                 hcd_tail[0..take].copy_from_slice(&lf[0..take]);
-                println!("v2 unpack: used leftovers {} (0x{:X} bytes)",
-                         std::path::Path::new(&leftovers_path).file_name()
-                             .unwrap_or(std::ffi::OsStr::new(""))
-                             .to_string_lossy(),
-                         take);
-                filled = true;
+                restored = Some(lf[0..take].to_vec());
+                if take < missing {
+                    if policy == LeftoversPolicy::Strict {
+                        return Err(ConversionError::LeftoversShort { expected: missing, actual: lf.len() });
+                    }
+                    warnings.push(format!(
+                        "leftovers cover 0x{:X} of 0x{:X} missing bytes — filling the rest with zeros.",
+                        take, missing
+                    ));
+                }
             }
-        }
-
-        if !filled {
-            // Fill with zeros if no leftovers file exists
-            if has_leftovers_flag {
-                println!("v2 unpack: marker indicates leftovers, but leftovers file not found — filling missing with zeros.");
+            None => {
+                if policy == LeftoversPolicy::Strict {
+                    return Err(ConversionError::LeftoversMissing);
+                }
+                // Fill with zeros if no leftovers were supplied
+                warnings.push("marker indicates leftovers, but no leftovers were supplied — filling missing with zeros.".to_string());
             }
         }
     }
 
     // Build the middle part: [first_8_bytes][middle_segment][hcd_full]
-    let middle_len = constants::PS4_SIZE - constants::MD5_HEADER_SIZE - constants::SAV_HEADER_SIZE - 1;
+    let middle_len = layout.middle_len();
     let mut middle = vec![0u8; middle_len];
 
     let mut m = 0;
-    middle[m..m + 8].copy_from_slice(first_8_bytes);      // [first_8_bytes] - first 8 bytes of middle
-    m += 8;
+    middle[m..m + lead].copy_from_slice(first_8_bytes);      // [first_8_bytes] - first 8 bytes of middle
+    m += lead;
     middle[m..m + middle_segment_len].copy_from_slice(middle_segment);  // [middle_segment] - middle segment
     m += middle_segment_len;
     middle[m..m + hcd_present_len].copy_from_slice(hcd_present);  // [hcd_section] - present hcd_section data
@@ -256,24 +414,272 @@ pub fn pcready_to_ps4(data: &[u8], input_path: &str, _dir: &str, has_leftovers_f
     }
 
     if m != middle_len {
-        return Err(anyhow::anyhow!("v2 unpack: middle length mismatch."));
+        return Err(ConversionError::SizeMismatch { what: "middle", expected: middle_len, actual: m });
     }
 
     // Reconstruct final PS4 format: [md5_header][sav_header][middle][z_byte]
-    let mut ps4 = vec![0u8; constants::PS4_SIZE];
-    ps4[0..constants::MD5_HEADER_SIZE].copy_from_slice(md5_header);  // [md5_header] - original first 0x20 bytes
-    ps4[constants::MD5_HEADER_SIZE..constants::MD5_HEADER_SIZE + constants::SAV_HEADER_SIZE].copy_from_slice(sav_header);  // [sav_header] - #SAV section
-    ps4[constants::MD5_HEADER_SIZE + constants::SAV_HEADER_SIZE..constants::MD5_HEADER_SIZE + constants::SAV_HEADER_SIZE + middle_len].copy_from_slice(&middle);  // [middle] - reconstructed middle with [first_8_bytes][middle_segment][hcd_section]
+    let mut ps4 = vec![0u8; layout.ps4_size];
+    ps4[0..md5_size].copy_from_slice(md5_header);  // [md5_header] - original first 0x20 bytes
+    ps4[md5_size..md5_size + sav_size].copy_from_slice(sav_header);  // [sav_header] - #SAV section
+    ps4[md5_size + sav_size..md5_size + sav_size + middle_len].copy_from_slice(&middle);  // [middle] - reconstructed middle with [first_8_bytes][middle_segment][hcd_section]
     let ps4_len = ps4.len();  // Store the length in a local variable
     ps4[ps4_len - 1] = z_byte;  // [z_byte] - last byte
 
     // Final sanity checks: #SAV should be at 0x20 (start of sav_header in PS4 format) and at 0xA0 (0x20 + 0x80)
-    if !marker::has_magic_at(&ps4, constants::MD5_HEADER_SIZE) {  // Check at 0x20
-        return Err(anyhow::anyhow!("v2 unpack produced PS4 without #SAV at 0x20."));
+    for offset in layout.ps4_magic_offsets() {
+        if !marker::has_magic_at(&ps4, offset) {
+            return Err(ConversionError::MissingMagic { offset });
+        }
     }
-    if !marker::has_magic_at(&ps4, constants::MD5_HEADER_SIZE + constants::SAV_HEADER_SIZE) {  // Check at 0xA0
-        return Err(anyhow::anyhow!("v2 unpack produced PS4 without #SAV at 0xA0."));
+
+    // A save unchanged since packing should come back as the PS4 save it was packed from
+    let original = match provenance::check_provenance(data) {
+        Ok(check) if check.status == ProvenanceStatus::Unmodified => check.record.map(|r| r.ps4_sha1),
+        _ => checked.filter(|h| h.pcready_sha1 == utils::sha1_hex(data)).map(|h| h.ps4_sha1),
+    };
+    if original.is_some_and(|sha1| sha1 != utils::sha1_hex(&ps4)) {
+        warnings.push("restored PS4 save differs from the one this PC-ready save was packed from.".to_string());
     }
 
-    Ok(ps4)
+    Ok(ConversionOutput { data: ps4, leftovers: restored, warnings, embedded })
+}
+
+/// Rejects leftovers that do not fit the missing HCD tail or belong to another save.
+/// An edited save no longer has the PC-ready SHA1 they were made for, but still carries the original MD5 header.
+fn check_leftovers(header: &LeftoversHeader, data: &[u8], layout: &SaveLayout, missing: usize) -> Result<()> {
+    let hcd_offset = layout.ps4_size - 1 - missing;
+    if header.hcd_offset != hcd_offset || header.length != missing {
+        return Err(ConversionError::LeftoversMismatch(format!(
+            "they cover 0x{:X} bytes at 0x{:X} but this save is missing 0x{:X} bytes at 0x{:X}",
+            header.length, header.hcd_offset, missing, hcd_offset
+        )));
+    }
+    let origin = &data[data.len() - layout.md5_header_size..];
+    if header.pcready_sha1 != utils::sha1_hex(data) && header.origin_sha1 != utils::sha1_hex(origin) {
+        return Err(ConversionError::LeftoversMismatch(format!(
+            "they belong to another save (converted from PS4 save {})", header.ps4_sha1
+        )));
+    }
+    Ok(())
+}
+
+/// `ps4_to_pcready` that hands any leftovers to `store`
+pub fn ps4_to_pcready_with_store(data: &[u8], store: &mut dyn LeftoversStore) -> Result<ConversionOutput> {
+    let out = ps4_to_pcready(data)?;
+    if let Some(lf) = &out.leftovers {
+        let key = LeftoversKey { ps4_sha1: Some(utils::sha1_hex(data)), ..leftovers_key(&out.data) };
+        store.store(&key, lf)
+            .map_err(|e| ConversionError::LeftoversStorage(e.to_string()))?;
+    }
+    Ok(out)
+}
+
+/// `convert_auto` that hands leftovers to `store` when packing and looks them up there when unpacking
+pub fn convert_auto_with_store(data: &[u8], store: &mut dyn LeftoversStore, policy: LeftoversPolicy) -> Result<ConversionOutput> {
+    if detect::detect(data).is_ok_and(|d| d.format == SaveFormat::Ps4) {
+        return ps4_to_pcready_with_store(data, store);
+    }
+    let layout = detect::layout_for_pcready(data);
+    let flagged = marker::try_read_marker_at(data, layout.marker_offset)
+        .is_some_and(|(_, flag)| flag == constants::FLAG_LEFTOVERS);
+    let leftovers = if flagged { load_leftovers(data, store)? } else { None };
+    convert_auto(data, leftovers.as_deref(), policy)
+}
+
+/// `pcready_to_ps4` that looks the leftovers up in `store` when the marker flags them
+pub fn pcready_to_ps4_with_store(data: &[u8], has_leftovers_flag: bool, store: &mut dyn LeftoversStore, policy: LeftoversPolicy) -> Result<ConversionOutput> {
+    let leftovers = if has_leftovers_flag { load_leftovers(data, store)? } else { None };
+    pcready_to_ps4(data, has_leftovers_flag, leftovers.as_deref(), policy)
+}
+
+/// Key the leftovers of the PC-ready save `data` are stored under: its SHA1, plus the vault key in its fill gap if any
+pub fn leftovers_key(data: &[u8]) -> LeftoversKey {
+    let layout = detect::layout_for_pcready(data);
+    let vault_key = pack_geometry(layout).ok()
+        .filter(|g| data.len() == layout.editor_size && g.fill_len <= layout.hcd_start_pc_ready)
+        .and_then(|g| embedded::read_vault_key(&data[layout.hcd_start_pc_ready - g.fill_len..layout.hcd_start_pc_ready]));
+    LeftoversKey { pcready_sha1: utils::sha1_hex(data), ps4_sha1: None, vault_key }
+}
+
+fn load_leftovers(data: &[u8], store: &dyn LeftoversStore) -> Result<Option<Vec<u8>>> {
+    let key = leftovers_key(data);
+    store.load(&key).map_err(|e| ConversionError::LeftoversStorage(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::DEFAULT_LAYOUT;
+    use crate::leftovers::MemoryStore;
+    use crate::test_support::{compressible_tail, random_tail, synthetic_ps4, TAIL_LEN};
+
+    fn marker_of(pcready: &[u8]) -> (u8, u8) {
+        marker::try_read_marker(pcready).expect("packed saves carry a marker")
+    }
+
+    /// Packs `ps4`, unpacks the result with the leftovers packing produced, and checks the original comes back
+    fn assert_round_trip(ps4: &[u8]) -> ConversionOutput {
+        let packed = ps4_to_pcready(ps4).unwrap();
+        assert_eq!(packed.data.len(), DEFAULT_LAYOUT.editor_size);
+        let unpacked = convert_auto(&packed.data, packed.leftovers.as_deref(), LeftoversPolicy::Strict).unwrap();
+        assert_eq!(unpacked.data, ps4);
+        assert!(unpacked.warnings.is_empty(), "{:?}", unpacked.warnings);
+        packed
+    }
+
+    #[test]
+    fn zero_tail_needs_no_leftovers() {
+        let packed = assert_round_trip(&synthetic_ps4(&[0; TAIL_LEN]));
+        assert_eq!(packed.leftovers, None);
+        assert!(!packed.embedded);
+        assert_eq!(marker_of(&packed.data), (constants::VER_V2, constants::FLAG_NO_LEFTOVERS));
+    }
+
+    #[test]
+    fn compressible_tail_travels_in_the_fill_gap() {
+        let packed = assert_round_trip(&synthetic_ps4(&compressible_tail()));
+        assert_eq!(packed.leftovers, None);
+        assert!(packed.embedded);
+        assert_eq!(marker_of(&packed.data), (constants::VER_V3, constants::FLAG_NO_LEFTOVERS));
+    }
+
+    #[test]
+    fn random_tail_is_kept_outside_with_a_vault_key() {
+        let ps4 = synthetic_ps4(&random_tail());
+        let packed = assert_round_trip(&ps4);
+        assert!(!packed.embedded);
+        assert_eq!(marker_of(&packed.data), (constants::VER_V2, constants::FLAG_LEFTOVERS));
+
+        let (header, lf) = leftovers::parse_leftovers(packed.leftovers.as_deref().unwrap()).unwrap().unwrap();
+        assert_eq!(lf, random_tail());
+        assert_eq!(header.ps4_sha1, utils::sha1_hex(&ps4));
+        assert_eq!(header.pcready_sha1, utils::sha1_hex(&packed.data));
+        assert_eq!(leftovers_key(&packed.data).vault_key.as_deref(), Some(&header.ps4_sha1[..embedded::VAULT_KEY_LEN]));
+    }
+
+    #[test]
+    fn store_round_trip() {
+        let ps4 = synthetic_ps4(&random_tail());
+        let mut store = MemoryStore::new();
+        let packed = ps4_to_pcready_with_store(&ps4, &mut store).unwrap();
+        let unpacked = convert_auto_with_store(&packed.data, &mut store, LeftoversPolicy::Strict).unwrap();
+        assert_eq!(unpacked.data, ps4);
+        let unpacked = pcready_to_ps4_with_store(&packed.data, true, &mut store, LeftoversPolicy::Strict).unwrap();
+        assert_eq!(unpacked.data, ps4);
+    }
+
+    #[test]
+    fn store_round_trip_of_an_embedded_tail() {
+        let ps4 = synthetic_ps4(&compressible_tail());
+        let mut store = MemoryStore::new();
+        let packed = ps4_to_pcready_with_store(&ps4, &mut store).unwrap();
+        assert!(packed.embedded);
+        let unpacked = convert_auto_with_store(&packed.data, &mut store, LeftoversPolicy::Strict).unwrap();
+        assert!(unpacked.embedded);
+        assert_eq!(unpacked.data, ps4);
+    }
+
+    #[test]
+    fn damaged_embedded_tail_fails_strict_and_zero_fills_otherwise() {
+        let packed = ps4_to_pcready(&synthetic_ps4(&compressible_tail())).unwrap();
+        let mut damaged = packed.data;
+        let gap_start = DEFAULT_LAYOUT.hcd_start_pc_ready - pack_geometry(&DEFAULT_LAYOUT).unwrap().fill_len;
+        damaged[gap_start + 5] ^= 0xFF;
+        assert_eq!(convert_auto(&damaged, None, LeftoversPolicy::Strict).unwrap_err(),
+                   ConversionError::EmbeddedLeftoversCorrupt("CRC mismatch"));
+        let filled = convert_auto(&damaged, None, LeftoversPolicy::ZeroFill).unwrap();
+        assert!(!filled.embedded);
+        assert!(filled.warnings[0].contains("CRC mismatch"), "{:?}", filled.warnings);
+    }
+
+    #[test]
+    fn missing_sidecar_fails_strict_and_zero_fills_otherwise() {
+        let ps4 = synthetic_ps4(&random_tail());
+        let packed = ps4_to_pcready(&ps4).unwrap();
+        assert_eq!(convert_auto(&packed.data, None, LeftoversPolicy::Strict).unwrap_err(), ConversionError::LeftoversMissing);
+
+        let filled = convert_auto(&packed.data, None, LeftoversPolicy::ZeroFill).unwrap();
+        assert!(filled.warnings.iter().any(|w| w.contains("no leftovers were supplied")), "{:?}", filled.warnings);
+        assert!(filled.warnings.iter().any(|w| w.contains("differs from the one")), "{:?}", filled.warnings);
+        assert_eq!(filled.data[..ps4.len() - 1 - TAIL_LEN], ps4[..ps4.len() - 1 - TAIL_LEN]);
+        assert!(filled.data[ps4.len() - 1 - TAIL_LEN..ps4.len() - 1].iter().all(|&b| b == 0));
+        assert_eq!(filled.data[ps4.len() - 1], ps4[ps4.len() - 1]);
+    }
+
+    #[test]
+    fn leftovers_of_another_save_are_refused() {
+        let packed = ps4_to_pcready(&synthetic_ps4(&random_tail())).unwrap();
+        let mut other = synthetic_ps4(&random_tail());
+        other[0] ^= 0xFF;
+        let other = ps4_to_pcready(&other).unwrap();
+        let err = convert_auto(&packed.data, other.leftovers.as_deref(), LeftoversPolicy::ZeroFill).unwrap_err();
+        assert!(matches!(&err, ConversionError::LeftoversMismatch(m) if m.contains("another save")), "{err:?}");
+    }
+
+    #[test]
+    fn leftovers_covering_other_bytes_are_refused() {
+        let packed = ps4_to_pcready(&synthetic_ps4(&random_tail())).unwrap();
+        let (mut header, lf) = leftovers::parse_leftovers(packed.leftovers.as_deref().unwrap()).unwrap().unwrap();
+        header.hcd_offset -= 1;
+        let moved = leftovers::encode_leftovers(&header, lf);
+        let err = convert_auto(&packed.data, Some(&moved), LeftoversPolicy::ZeroFill).unwrap_err();
+        assert!(matches!(&err, ConversionError::LeftoversMismatch(m) if m.contains("cover")), "{err:?}");
+    }
+
+    #[test]
+    fn legacy_sidecar_is_used_unchecked() {
+        let ps4 = synthetic_ps4(&random_tail());
+        let packed = ps4_to_pcready(&ps4).unwrap();
+        let (_, lf) = leftovers::parse_leftovers(packed.leftovers.as_deref().unwrap()).unwrap().unwrap();
+        let unpacked = convert_auto(&packed.data, Some(lf), LeftoversPolicy::Strict).unwrap();
+        assert_eq!(unpacked.data, ps4);
+        assert!(unpacked.warnings.iter().any(|w| w.contains("legacy sidecar")), "{:?}", unpacked.warnings);
+    }
+
+    #[test]
+    fn short_legacy_sidecar_fails_strict_and_zero_fills_otherwise() {
+        let packed = ps4_to_pcready(&synthetic_ps4(&random_tail())).unwrap();
+        let (_, lf) = leftovers::parse_leftovers(packed.leftovers.as_deref().unwrap()).unwrap().unwrap();
+        let short = &lf[..TAIL_LEN - 0x10];
+        assert_eq!(convert_auto(&packed.data, Some(short), LeftoversPolicy::Strict).unwrap_err(),
+                   ConversionError::LeftoversShort { expected: TAIL_LEN, actual: TAIL_LEN - 0x10 });
+        let filled = convert_auto(&packed.data, Some(short), LeftoversPolicy::ZeroFill).unwrap();
+        assert!(filled.warnings.iter().any(|w| w.contains("cover 0x90 of 0xA0")), "{:?}", filled.warnings);
+    }
+
+    #[test]
+    fn long_legacy_sidecar_is_refused() {
+        let packed = ps4_to_pcready(&synthetic_ps4(&random_tail())).unwrap();
+        let (_, lf) = leftovers::parse_leftovers(packed.leftovers.as_deref().unwrap()).unwrap().unwrap();
+        let mut long = lf.to_vec();
+        long.push(0);
+        for policy in [LeftoversPolicy::Strict, LeftoversPolicy::ZeroFill] {
+            let err = convert_auto(&packed.data, Some(&long), policy).unwrap_err();
+            assert!(matches!(&err, ConversionError::LeftoversMismatch(m) if m.contains("0xA1 bytes")), "{err:?}");
+        }
+    }
+
+    #[test]
+    fn wrong_size_is_refused() {
+        let ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        assert_eq!(ps4_to_pcready(&ps4[1..]).unwrap_err(),
+                   ConversionError::WrongSize { expected: DEFAULT_LAYOUT.ps4_size, actual: ps4.len() - 1 });
+        let packed = ps4_to_pcready(&ps4).unwrap();
+        assert_eq!(pcready_to_ps4(&packed.data[1..], false, None, LeftoversPolicy::Strict).unwrap_err(),
+                   ConversionError::WrongSize { expected: DEFAULT_LAYOUT.editor_size, actual: packed.data.len() - 1 });
+    }
+
+    #[test]
+    fn missing_magic_is_refused() {
+        let mut ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        let packed = ps4_to_pcready(&ps4).unwrap();
+        ps4[0xA0] = 0;
+        assert_eq!(ps4_to_pcready(&ps4).unwrap_err(), ConversionError::MissingMagic { offset: 0xA0 });
+
+        let mut pcready = packed.data;
+        let sav = DEFAULT_LAYOUT.pcready_sav_header_offset();
+        pcready[sav] = 0;
+        assert_eq!(pcready_to_ps4(&pcready, false, None, LeftoversPolicy::Strict).unwrap_err(),
+                   ConversionError::MissingMagic { offset: sav });
+    }
 }
@@ -0,0 +1,187 @@
+use crate::error::{ConversionError, Result};
+use crate::layout::{SaveLayout, KNOWN_LAYOUTS};
+use crate::marker;
+
+/// Which side of the conversion a save is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SaveFormat {
+    /// PS4 save: [MD5_HEADER][SAV_HEADER][middle][Z_BYTE]
+    Ps4,
+    /// PC-ready save: [processed data with marker][Z_BYTE][SAV_HEADER][MD5_HEADER]
+    PcReady,
+}
+
+impl SaveFormat {
+    pub fn name(&self) -> &'static str {
+        match self {
+            SaveFormat::Ps4 => "PS4",
+            SaveFormat::PcReady => "PC-ready",
+        }
+    }
+}
+
+/// Format and game-version layout a save was recognized as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Detection {
+    pub format: SaveFormat,
+    pub layout: &'static SaveLayout,
+}
+
+/// Picks the format and layout from the file size plus the '#SAV' / marker positions
+pub fn detect(data: &[u8]) -> Result<Detection> {
+    for layout in KNOWN_LAYOUTS {
+        if is_ps4(data, layout) {
+            return Ok(Detection { format: SaveFormat::Ps4, layout });
+        }
+        if is_pcready(data, layout) {
+            return Ok(Detection { format: SaveFormat::PcReady, layout });
+        }
+    }
+    Err(ConversionError::NoMatchingLayout { size: data.len(), diagnosis: diagnose(data) })
+}
+
+/// Layout whose PS4 size matches `data`, falling back to the default so size errors name it
+pub fn layout_for_ps4(data: &[u8]) -> &'static SaveLayout {
+    KNOWN_LAYOUTS.iter()
+        .find(|l| is_ps4(data, l))
+        .or_else(|| KNOWN_LAYOUTS.iter().find(|l| l.ps4_size == data.len()))
+        .unwrap_or(&KNOWN_LAYOUTS[0])
+}
+
+/// Layout whose PC-ready size matches `data`, falling back to the default so size errors name it
+pub fn layout_for_pcready(data: &[u8]) -> &'static SaveLayout {
+    KNOWN_LAYOUTS.iter()
+        .find(|l| is_pcready(data, l))
+        .or_else(|| KNOWN_LAYOUTS.iter().find(|l| l.editor_size == data.len()))
+        .unwrap_or(&KNOWN_LAYOUTS[0])
+}
+
+fn is_ps4(data: &[u8], layout: &SaveLayout) -> bool {
+    data.len() == layout.ps4_size
+        && layout.ps4_magic_offsets().iter().all(|&o| marker::has_magic_at(data, o))
+}
+
+fn is_pcready(data: &[u8], layout: &SaveLayout) -> bool {
+    data.len() == layout.editor_size
+        && marker::try_read_marker_at(data, layout.marker_offset).is_some()
+        && marker::has_magic_at(data, layout.pcready_sav_header_offset())
+}
+
+/// Explains why no layout matched
+fn diagnose(data: &[u8]) -> String {
+    let mut notes = Vec::new();
+
+    for layout in KNOWN_LAYOUTS {
+        if data.len() == layout.ps4_size {
+            let missing: Vec<String> = layout.ps4_magic_offsets().iter()
+                .filter(|&&o| !marker::has_magic_at(data, o))
+                .map(|o| format!("0x{:X}", o))
+                .collect();
+            notes.push(format!(
+                "size matches the '{}' PS4 layout but '#SAV' is missing at {}",
+                layout.name, missing.join(" and ")
+            ));
+        }
+        if data.len() == layout.editor_size {
+            if marker::try_read_marker_at(data, layout.marker_offset).is_none() {
+                notes.push(format!(
+                    "size matches the '{}' PC-ready layout but there is no valid marker at 0x{:X}",
+                    layout.name, layout.marker_offset
+                ));
+            } else {
+                notes.push(format!(
+                    "size matches the '{}' PC-ready layout but '#SAV' is missing at 0x{:X}",
+                    layout.name, layout.pcready_sav_header_offset()
+                ));
+            }
+        }
+    }
+
+    if notes.is_empty() {
+        // No size matched: name the closest known size and what the content looks like
+        let closest = KNOWN_LAYOUTS.iter()
+            .flat_map(|l| [(l, SaveFormat::Ps4, l.ps4_size), (l, SaveFormat::PcReady, l.editor_size)])
+            .min_by_key(|(_, _, size)| size.abs_diff(data.len()));
+        if let Some((layout, format, size)) = closest {
+            let sign = if data.len() > size { "+" } else { "-" };
+            notes.push(format!(
+                "size 0x{:X} matches no known layout; closest is the '{}' {} layout (0x{:X}, {}0x{:X} bytes)",
+                data.len(), layout.name, format.name(), size, sign, size.abs_diff(data.len())
+            ));
+        }
+        if marker::has_dual_magic(data) {
+            notes.push("'#SAV' found at 0x20 and 0xA0, so the content looks like a PS4 save".to_string());
+        } else if marker::has_any_marker_at_08(data) {
+            notes.push("a converter marker was found at 0x08, so the content looks like a PC-ready save".to_string());
+        }
+    }
+
+    notes.join("; ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::ps4_to_pcready;
+    use crate::layout::DEFAULT_LAYOUT;
+    use crate::test_support::{synthetic_ps4, TAIL_LEN};
+
+    fn diagnosis_of(data: &[u8]) -> String {
+        match detect(data) {
+            Err(ConversionError::NoMatchingLayout { size, diagnosis }) => {
+                assert_eq!(size, data.len());
+                diagnosis
+            }
+            other => panic!("expected no matching layout, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn ps4_save() {
+        let ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        assert_eq!(detect(&ps4), Ok(Detection { format: SaveFormat::Ps4, layout: &KNOWN_LAYOUTS[0] }));
+        assert_eq!(layout_for_ps4(&ps4).name, DEFAULT_LAYOUT.name);
+    }
+
+    #[test]
+    fn pcready_save() {
+        let pcready = ps4_to_pcready(&synthetic_ps4(&[0; TAIL_LEN])).unwrap().data;
+        assert_eq!(detect(&pcready), Ok(Detection { format: SaveFormat::PcReady, layout: &KNOWN_LAYOUTS[0] }));
+        assert_eq!(layout_for_pcready(&pcready).name, DEFAULT_LAYOUT.name);
+    }
+
+    #[test]
+    fn wrong_size_names_the_closest_layout() {
+        let mut ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        ps4.extend([0; 0x10]);
+        let diagnosis = diagnosis_of(&ps4);
+        assert!(diagnosis.contains("closest is the 'default' PS4 layout (0x12A200, +0x10 bytes)"), "{diagnosis}");
+        assert!(diagnosis.contains("looks like a PS4 save"), "{diagnosis}");
+        // Layout lookups fall back to the default so size errors can name it
+        assert_eq!(layout_for_ps4(&ps4).name, DEFAULT_LAYOUT.name);
+        assert_eq!(layout_for_pcready(&[0; 4]).name, DEFAULT_LAYOUT.name);
+    }
+
+    #[test]
+    fn missing_dual_magic() {
+        let mut ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        ps4[0xA0] = 0;
+        assert_eq!(diagnosis_of(&ps4), "size matches the 'default' PS4 layout but '#SAV' is missing at 0xA0");
+        ps4[0x20] = 0;
+        assert_eq!(diagnosis_of(&ps4), "size matches the 'default' PS4 layout but '#SAV' is missing at 0x20 and 0xA0");
+    }
+
+    #[test]
+    fn unknown_marker_version() {
+        let mut pcready = ps4_to_pcready(&synthetic_ps4(&[0; TAIL_LEN])).unwrap().data;
+        pcready[DEFAULT_LAYOUT.marker_offset + 7] = 0x7F;
+        assert_eq!(diagnosis_of(&pcready), "size matches the 'default' PC-ready layout but there is no valid marker at 0x8");
+    }
+
+    #[test]
+    fn pcready_without_sav_header() {
+        let mut pcready = ps4_to_pcready(&synthetic_ps4(&[0; TAIL_LEN])).unwrap().data;
+        pcready[DEFAULT_LAYOUT.pcready_sav_header_offset()] = 0;
+        assert_eq!(diagnosis_of(&pcready), "size matches the 'default' PC-ready layout but '#SAV' is missing at 0x12A158");
+    }
+}
@@ -0,0 +1,425 @@
+use crate::constants::*;
+use crate::detect::{self, Detection, SaveFormat};
+use crate::inspect::find_magic;
+use crate::layout::{SaveLayout, KNOWN_LAYOUTS};
+use crate::marker;
+
+/// Which check a finding comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Check {
+    Size,
+    Magic,
+    Marker,
+    Truncation,
+    Encryption,
+}
+
+impl Check {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Check::Size => "size",
+            Check::Magic => "magic",
+            Check::Marker => "marker",
+            Check::Truncation => "truncation",
+            Check::Encryption => "encryption",
+        }
+    }
+}
+
+/// One problem `doctor` noticed, with what to do about it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Finding {
+    pub check: Check,
+    pub summary: String,
+    pub suggestion: String,
+}
+
+/// Result of `doctor`: the detection if the save is recognized, otherwise why it is not
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnosis {
+    pub detected: Option<Detection>,
+    pub findings: Vec<Finding>,
+}
+
+/// Bits of entropy per byte above which a file without '#SAV' is taken for encrypted data
+const ENCRYPTED_ENTROPY: f64 = 7.5;
+
+/// Explains why `data` is not recognized as a save, one finding per check that failed
+pub fn doctor(data: &[u8]) -> Diagnosis {
+    if let Ok(detected) = detect::detect(data) {
+        return Diagnosis { detected: Some(detected), findings: Vec::new() };
+    }
+
+    let (layout, format, size) = reference(data);
+    let mut findings = Vec::new();
+    findings.extend(check_size(data, format, size));
+    findings.extend(check_magic(data, layout, format));
+    findings.extend(check_marker(data, layout, format));
+    findings.extend(check_truncation(data, layout, format, size));
+    findings.extend(check_encryption(data));
+    Diagnosis { detected: None, findings }
+}
+
+/// (layout, format, size) to judge `data` against: the one its size matches, else what its content
+/// looks like (a '#SAV' pair means PS4, a marker signature PC-ready), else the closest size
+fn reference(data: &[u8]) -> (&'static SaveLayout, SaveFormat, usize) {
+    let closest = closest_size(data.len());
+    if KNOWN_LAYOUTS.iter().any(|l| data.len() == l.ps4_size || data.len() == l.editor_size) {
+        return closest;
+    }
+    let layout = closest.0;
+    let found = find_magic(data);
+    if found.iter().any(|o| found.contains(&(o + layout.sav_header_size))) {
+        (layout, SaveFormat::Ps4, layout.ps4_size)
+    } else if has_marker_signature(data, layout.marker_offset) {
+        (layout, SaveFormat::PcReady, layout.editor_size)
+    } else {
+        closest
+    }
+}
+
+/// The fixed bytes of a marker at `o`, whatever its flag and version
+fn has_marker_signature(data: &[u8], o: usize) -> bool {
+    data.len() >= o + MARKER_LEN
+        && data[o..o + 5] == [MARK0, MARK1, MARK2, MARK3, MARK4]
+        && data[o + 6] == MARK6
+}
+
+/// Known (layout, format, size) closest to `len`
+fn closest_size(len: usize) -> (&'static SaveLayout, SaveFormat, usize) {
+    KNOWN_LAYOUTS.iter()
+        .flat_map(|l| [(l, SaveFormat::Ps4, l.ps4_size), (l, SaveFormat::PcReady, l.editor_size)])
+        .min_by_key(|(_, _, size)| size.abs_diff(len))
+        .expect("KNOWN_LAYOUTS is not empty")
+}
+
+/// Offsets where '#SAV' belongs in a save of `format` and `layout`
+fn expected_magic(layout: &SaveLayout, format: SaveFormat) -> Vec<usize> {
+    match format {
+        SaveFormat::Ps4 => layout.ps4_magic_offsets().to_vec(),
+        SaveFormat::PcReady => vec![layout.pcready_sav_header_offset()],
+    }
+}
+
+fn distance(len: usize, size: usize) -> String {
+    match len.cmp(&size) {
+        std::cmp::Ordering::Less => format!("0x{:X} bytes short", size - len),
+        std::cmp::Ordering::Greater => format!("0x{:X} bytes over", len - size),
+        std::cmp::Ordering::Equal => "exact".to_string(),
+    }
+}
+
+fn check_size(data: &[u8], format: SaveFormat, size: usize) -> Option<Finding> {
+    let matches = KNOWN_LAYOUTS.iter().any(|l| data.len() == l.ps4_size || data.len() == l.editor_size);
+    if matches {
+        return None;
+    }
+
+    let distances: Vec<String> = KNOWN_LAYOUTS.iter()
+        .flat_map(|l| [(l, SaveFormat::Ps4, l.ps4_size), (l, SaveFormat::PcReady, l.editor_size)])
+        .map(|(l, format, size)| format!("'{}' {} 0x{:X} ({})", l.name, format.name(), size, distance(data.len(), size)))
+        .collect();
+    let suggestion = if data.len() < size {
+        format!("The file is smaller than a {} save; copy it again from the source, an interrupted copy leaves it short.",
+                format.name())
+    } else {
+        format!("The file is larger than a {} save; check it is a Xenoverse 2 save and not an archive or a save of \
+                 another game, or of a newer game version.", format.name())
+    };
+    Some(Finding {
+        check: Check::Size,
+        summary: format!("size 0x{:X} matches no known layout: {}", data.len(), distances.join(", ")),
+        suggestion,
+    })
+}
+
+fn check_magic(data: &[u8], layout: &SaveLayout, format: SaveFormat) -> Option<Finding> {
+    let expected = expected_magic(layout, format);
+    let found = find_magic(data);
+    if expected.iter().all(|o| found.contains(o)) {
+        return None;
+    }
+
+    let list = |offsets: &[usize]| offsets.iter().map(|o| format!("0x{:X}", o)).collect::<Vec<_>>().join(", ");
+    let summary = format!("'#SAV' expected at {} for a {} save but found at {}", list(&expected), format.name(),
+                          if found.is_empty() { "no offset".to_string() } else { list(&found) });
+
+    // The two PS4 magics sit sav_header_size apart; the same pair elsewhere means bytes were added or removed in front
+    let pair = found.iter().copied()
+        .find(|o| found.contains(&(o + layout.sav_header_size)));
+    let ps4_first = layout.ps4_magic_offsets()[0];
+    let zero_start = data.len() - data.iter().rev().take_while(|&&b| b == 0).count();
+    let pc_from_end = layout.md5_header_size + layout.sav_header_size;
+    let suggestion = match pair {
+        Some(o) if o + layout.md5_header_size == ps4_first => format!(
+            "This looks like a PS4 save without its 0x{:X}-byte MD5 header; use the complete export, which is 0x{:X} bytes.",
+            layout.md5_header_size, layout.ps4_size),
+        Some(o) if o > ps4_first => format!(
+            "This looks like a PS4 save with 0x{:X} extra bytes in front; remove the first 0x{:X} bytes.",
+            o - ps4_first, o - ps4_first),
+        Some(o) if o < ps4_first => format!(
+            "This looks like a PS4 save missing its first 0x{:X} bytes; copy it again from the source.", ps4_first - o),
+        _ if found.last().is_some_and(|&o| data.len() >= pc_from_end && o == data.len() - pc_from_end) => format!(
+            "'#SAV' sits 0x{:X} bytes before the end, where a PC-ready save keeps it, so this is a PC-ready save whose \
+             body changed size; save it again from an editor that keeps the {} layout size (0x{:X} bytes).",
+            pc_from_end, layout.name, layout.editor_size),
+        _ if expected.iter().all(|&o| o >= zero_start) => "Where '#SAV' belongs the file is blank; it was not \
+             copied completely (see the truncation finding).".to_string(),
+        _ if expected.iter().any(|o| found.contains(o)) => format!(
+            "The '#SAV' header at {} was overwritten; restore the file from a backup or export it again.",
+            list(&expected.iter().copied().filter(|o| !found.contains(o)).collect::<Vec<_>>())),
+        _ if found.is_empty() => "No '#SAV' header anywhere: this is not a plain Xenoverse 2 save (see the other findings \
+             for whether it is encrypted or blank).".to_string(),
+        _ => "'#SAV' is not where any known layout has it; the save may come from an unsupported game version. \
+              Keep the file and report it along with the offsets above.".to_string(),
+    };
+    Some(Finding { check: Check::Magic, summary, suggestion })
+}
+
+fn check_marker(data: &[u8], layout: &SaveLayout, format: SaveFormat) -> Option<Finding> {
+    let o = layout.marker_offset;
+    let has_signature = |o: usize| has_marker_signature(data, o);
+
+    if has_signature(o) {
+        if marker::try_read_marker_at(data, o).is_some() {
+            return None;
+        }
+        let (flag, version) = (data[o + 5], data[o + 7]);
+        let mut problems = Vec::new();
+        let mut suggestions = Vec::new();
+        if version != VER_V2 && version != VER_V3 {
+            problems.push(format!("version 0x{:02X} (this build knows 0x{:02X} and 0x{:02X})", version, VER_V2, VER_V3));
+            suggestions.push("It was written by a different converter version; convert it back with the version that \
+                              produced it, or update this one.".to_string());
+        }
+        if flag != FLAG_NO_LEFTOVERS && flag != FLAG_LEFTOVERS {
+            problems.push(format!("leftovers flag 0x{:02X} (expected 0x{:02X} or 0x{:02X})", flag, FLAG_NO_LEFTOVERS, FLAG_LEFTOVERS));
+            suggestions.push(format!(
+                "The flag byte at 0x{:X} was probably overwritten by an editor; set it to 0x{:02X} if a leftovers sidecar \
+                 exists for this save, otherwise to 0x{:02X}.", o + 5, FLAG_LEFTOVERS, FLAG_NO_LEFTOVERS));
+        }
+        return Some(Finding {
+            check: Check::Marker,
+            summary: format!("marker at 0x{:X} has an unknown {}", o, problems.join(" and ")),
+            suggestion: suggestions.join(" "),
+        });
+    }
+
+    if let Some(found) = (0..=data.len().saturating_sub(MARKER_LEN)).find(|&i| has_signature(i)) {
+        return Some(Finding {
+            check: Check::Marker,
+            summary: format!("marker found at 0x{:X} instead of 0x{:X}", found, o),
+            suggestion: if found > o {
+                format!("0x{:X} bytes were inserted before the marker; remove them.", found - o)
+            } else {
+                format!("0x{:X} bytes are missing before the marker; copy the file again from the source.", o - found)
+            },
+        });
+    }
+
+    // A save of PC-ready size without any marker was not produced by this converter
+    (format == SaveFormat::PcReady && data.len() == layout.editor_size).then(|| Finding {
+        check: Check::Marker,
+        summary: format!("no converter marker at 0x{:X}", o),
+        suggestion: "PC-ready saves carry a marker written when they were converted from PS4; convert the original \
+                     PS4 save again, or if this is the game's own PC save, it is not in PC-ready form.".to_string(),
+    })
+}
+
+fn check_truncation(data: &[u8], layout: &SaveLayout, format: SaveFormat, size: usize) -> Option<Finding> {
+    let zeros = data.iter().rev().take_while(|&&b| b == 0).count();
+    let zero_start = data.len() - zeros;
+
+    if data.len() > size && zero_start <= size {
+        return Some(Finding {
+            check: Check::Truncation,
+            summary: format!("zero-padded: the 0x{:X} bytes past the '{}' {} size are all zero",
+                             data.len() - size, layout.name, format.name()),
+            suggestion: format!("Cut the file down to {} bytes (e.g. `truncate -s {} FILE`), then convert it.", size, size),
+        });
+    }
+
+    let last_magic = expected_magic(layout, format).into_iter().max().unwrap_or(0);
+    if data.is_empty() || (zeros > 0 && zero_start <= last_magic) {
+        return Some(Finding {
+            check: Check::Truncation,
+            summary: format!("the file is zero from 0x{:X} to its end (0x{:X} bytes), covering where '#SAV' belongs",
+                             zero_start, zeros),
+            suggestion: "The copy was cut off after space for it was reserved; copy the file again from the source."
+                .to_string(),
+        });
+    }
+
+    // A shifted '#SAV' pair means the bytes are missing in front, which the magic finding already explains
+    let found = find_magic(data);
+    let shifted = format == SaveFormat::Ps4
+        && !found.is_empty()
+        && !expected_magic(layout, format).iter().all(|o| found.contains(o));
+    (data.len() < size && !shifted).then(|| Finding {
+        check: Check::Truncation,
+        summary: format!("truncated: 0x{:X} bytes short of the '{}' {} size", size - data.len(), layout.name, format.name()),
+        suggestion: "The end of the file is missing; copy it again from the source.".to_string(),
+    })
+}
+
+fn check_encryption(data: &[u8]) -> Option<Finding> {
+    let bits = entropy(data);
+    (bits > ENCRYPTED_ENTROPY && find_magic(data).is_empty()).then(|| Finding {
+        check: Check::Encryption,
+        summary: format!("the content looks encrypted ({:.2} bits of entropy per byte, no '#SAV' anywhere)", bits),
+        suggestion: "This is probably the game's encrypted PC save; decrypt it with a Xenoverse 2 save tool first, \
+                     or use the PC-ready file this converter produced."
+            .to_string(),
+    })
+}
+
+/// Shannon entropy of `data` in bits per byte
+fn entropy(data: &[u8]) -> f64 {
+    if data.is_empty() {
+        return 0.0;
+    }
+    let mut counts = [0usize; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    let len = data.len() as f64;
+    counts.iter()
+        .filter(|&&c| c > 0)
+        .map(|&c| {
+            let p = c as f64 / len;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::ps4_to_pcready;
+    use crate::layout::DEFAULT_LAYOUT;
+    use crate::test_support::{synthetic_ps4, TAIL_LEN};
+
+    fn ps4() -> Vec<u8> {
+        synthetic_ps4(&[0; TAIL_LEN])
+    }
+
+    fn pcready() -> Vec<u8> {
+        ps4_to_pcready(&ps4()).unwrap().data
+    }
+
+    /// The finding of `check`, which must be there
+    fn finding(diagnosis: &Diagnosis, check: Check) -> &Finding {
+        diagnosis.findings.iter().find(|f| f.check == check)
+            .unwrap_or_else(|| panic!("no {} finding in {:?}", check.name(), diagnosis.findings))
+    }
+
+    fn checks(diagnosis: &Diagnosis) -> Vec<Check> {
+        diagnosis.findings.iter().map(|f| f.check).collect()
+    }
+
+    #[test]
+    fn recognized_saves_have_no_findings() {
+        for data in [ps4(), pcready()] {
+            let diagnosis = doctor(&data);
+            assert_eq!(diagnosis.detected, detect::detect(&data).ok());
+            assert!(diagnosis.findings.is_empty());
+        }
+    }
+
+    #[test]
+    fn truncated_ps4_save() {
+        let mut data = ps4();
+        data.truncate(DEFAULT_LAYOUT.ps4_size - 0x10);
+        let diagnosis = doctor(&data);
+        assert_eq!(checks(&diagnosis), vec![Check::Size, Check::Truncation]);
+        assert!(finding(&diagnosis, Check::Size).summary.contains("'default' PS4 0x12A200 (0x10 bytes short)"));
+        assert_eq!(finding(&diagnosis, Check::Truncation).summary, "truncated: 0x10 bytes short of the 'default' PS4 size");
+    }
+
+    #[test]
+    fn ps4_save_without_md5_header() {
+        let data = ps4()[DEFAULT_LAYOUT.md5_header_size..].to_vec();
+        let diagnosis = doctor(&data);
+        assert_eq!(checks(&diagnosis), vec![Check::Size, Check::Magic]);
+        assert!(finding(&diagnosis, Check::Magic).suggestion.contains("without its 0x20-byte MD5 header"));
+    }
+
+    #[test]
+    fn overwritten_sav_header() {
+        let mut data = ps4();
+        data[0xA0] = 0;
+        let diagnosis = doctor(&data);
+        assert_eq!(checks(&diagnosis), vec![Check::Magic]);
+        assert!(finding(&diagnosis, Check::Magic).suggestion.contains("'#SAV' header at 0xA0 was overwritten"));
+    }
+
+    #[test]
+    fn unknown_marker_version_and_flag() {
+        let mut data = pcready();
+        data[DEFAULT_LAYOUT.marker_offset + 7] = 0x7F;
+        data[DEFAULT_LAYOUT.marker_offset + 5] = 0;
+        let diagnosis = doctor(&data);
+        assert_eq!(checks(&diagnosis), vec![Check::Marker]);
+        assert_eq!(finding(&diagnosis, Check::Marker).summary,
+                   "marker at 0x8 has an unknown version 0x7F (this build knows 0x31 and 0x32) and leftovers flag 0x00 (expected 0x54 or 0x2B)");
+    }
+
+    #[test]
+    fn shifted_marker() {
+        let mut data = vec![0xAA; 4];
+        data.extend(pcready());
+        let diagnosis = doctor(&data);
+        assert_eq!(finding(&diagnosis, Check::Marker).summary, "marker found at 0xC instead of 0x8");
+        assert!(finding(&diagnosis, Check::Marker).suggestion.starts_with("0x4 bytes were inserted"));
+    }
+
+    #[test]
+    fn marker_in_the_last_bytes_is_found() {
+        let mut data = vec![0u8; 0x100];
+        data[0xF8..].copy_from_slice(&marker::make_marker(VER_V2, FLAG_NO_LEFTOVERS));
+        let diagnosis = doctor(&data);
+        assert_eq!(finding(&diagnosis, Check::Marker).summary, "marker found at 0xF8 instead of 0x8");
+    }
+
+    #[test]
+    fn pcready_size_without_marker() {
+        let mut data = pcready();
+        data[DEFAULT_LAYOUT.marker_offset..DEFAULT_LAYOUT.marker_offset + MARKER_LEN].fill(0);
+        let diagnosis = doctor(&data);
+        assert_eq!(finding(&diagnosis, Check::Marker).summary, "no converter marker at 0x8");
+    }
+
+    #[test]
+    fn zero_padded_save() {
+        let mut data = ps4();
+        data.extend([0; 0x200]);
+        let diagnosis = doctor(&data);
+        assert_eq!(checks(&diagnosis), vec![Check::Size, Check::Truncation]);
+        assert!(finding(&diagnosis, Check::Truncation).summary.starts_with("zero-padded: the 0x200 bytes past"));
+    }
+
+    #[test]
+    fn blank_after_reserving_space() {
+        let mut data = ps4();
+        data[0x10..].fill(0);
+        let diagnosis = doctor(&data);
+        assert_eq!(checks(&diagnosis), vec![Check::Magic, Check::Truncation]);
+        assert!(finding(&diagnosis, Check::Truncation).summary.contains("covering where '#SAV' belongs"));
+        assert!(finding(&diagnosis, Check::Magic).suggestion.contains("the file is blank"));
+    }
+
+    #[test]
+    fn encrypted_save() {
+        let mut state = 0x9E37_79B9u32;
+        let data: Vec<u8> = (0..DEFAULT_LAYOUT.editor_size).map(|_| {
+            state ^= state << 13;
+            state ^= state >> 17;
+            state ^= state << 5;
+            state as u8
+        }).collect();
+        let diagnosis = doctor(&data);
+        assert!(checks(&diagnosis).contains(&Check::Encryption), "{:?}", diagnosis.findings);
+        assert!(entropy(&data) > ENCRYPTED_ENTROPY);
+        assert_eq!(entropy(&[7; 64]), 0.0);
+    }
+}
@@ -0,0 +1,266 @@
+//! Records kept in the fill gap of a PC-ready save.
+//!
+//! v3 saves hold the leftovers themselves (little endian), followed by zeros up to the end of the gap:
+//! [encoding u8][decoded length u16][payload length u16][CRC-32 of the decoded bytes u32][payload]
+//! The decoded bytes are the trimmed HCD tail without its trailing zeros; unpacking pads them back with zeros.
+//!
+//! v2 saves whose leftovers are kept outside hold a vault key instead, naming them in a leftovers vault
+//! however the save is renamed, moved or edited: ["XV2K"][16 lowercase hex digits]
+//!
+//! Either way the last bytes of the gap hold the provenance record (see `provenance`).
+
+use crate::error::{ConversionError, Result};
+use crate::utils;
+
+const HEADER_LEN: usize = 9;
+
+/// Payload is the decoded bytes as-is
+const ENCODING_RAW: u8 = 0;
+/// Payload is PackBits run-length encoded
+const ENCODING_PACKBITS: u8 = 1;
+
+const VAULT_KEY_MAGIC: &[u8; 4] = b"XV2K";
+/// Hex digits in a vault key (the first 8 bytes of the original PS4 save's SHA1)
+pub const VAULT_KEY_LEN: usize = 16;
+
+/// Vault key record for the PS4 save with SHA1 `ps4_sha1`.
+/// The key cannot come from the PC-ready SHA1, which the vault files the leftovers under: the key is written into
+/// the PC-ready save, so it would change the SHA1 it is derived from. The PS4 SHA1 is fixed before packing and stays
+/// the same when the PC-ready save is edited, which is when the key is needed (an unedited save is found by its SHA1).
+pub fn encode_vault_key(ps4_sha1: &str) -> Vec<u8> {
+    let mut record = VAULT_KEY_MAGIC.to_vec();
+    record.extend_from_slice(&ps4_sha1.as_bytes()[..VAULT_KEY_LEN]);
+    record
+}
+
+/// Vault key in `gap`, if it holds a vault key record
+pub fn read_vault_key(gap: &[u8]) -> Option<String> {
+    let key = gap.strip_prefix(VAULT_KEY_MAGIC)?.get(..VAULT_KEY_LEN)?;
+    key.iter().all(|b| b.is_ascii_hexdigit()).then(|| String::from_utf8_lossy(key).to_lowercase())
+}
+
+/// Record for `tail` if it fits in `capacity` bytes, using whichever encoding is smaller
+pub fn encode(tail: &[u8], capacity: usize) -> Option<Vec<u8>> {
+    let len = tail.len() - tail.iter().rev().take_while(|&&b| b == 0).count();
+    let decoded = &tail[..len];
+
+    let packed = packbits(decoded);
+    let (encoding, payload) = if packed.len() < decoded.len() {
+        (ENCODING_PACKBITS, packed)
+    } else {
+        (ENCODING_RAW, decoded.to_vec())
+    };
+    if HEADER_LEN + payload.len() > capacity {
+        return None;
+    }
+
+    let mut record = Vec::with_capacity(HEADER_LEN + payload.len());
+    record.push(encoding);
+    record.extend_from_slice(&(decoded.len() as u16).to_le_bytes());
+    record.extend_from_slice(&(payload.len() as u16).to_le_bytes());
+    record.extend_from_slice(&utils::crc32(decoded).to_le_bytes());
+    record.extend_from_slice(&payload);
+    Some(record)
+}
+
+/// The `tail_len`-byte HCD tail stored in `gap`
+pub fn decode(gap: &[u8], tail_len: usize) -> Result<Vec<u8>> {
+    if gap.len() < HEADER_LEN {
+        return Err(ConversionError::EmbeddedLeftoversCorrupt("fill gap too small for a record"));
+    }
+    let encoding = gap[0];
+    let decoded_len = u16::from_le_bytes([gap[1], gap[2]]) as usize;
+    let payload_len = u16::from_le_bytes([gap[3], gap[4]]) as usize;
+    let crc = u32::from_le_bytes([gap[5], gap[6], gap[7], gap[8]]);
+
+    let payload = gap.get(HEADER_LEN..HEADER_LEN + payload_len)
+        .ok_or(ConversionError::EmbeddedLeftoversCorrupt("payload runs past the fill gap"))?;
+    let mut tail = match encoding {
+        ENCODING_RAW => payload.to_vec(),
+        ENCODING_PACKBITS => unpackbits(payload)
+            .ok_or(ConversionError::EmbeddedLeftoversCorrupt("bad run-length data"))?,
+        _ => return Err(ConversionError::EmbeddedLeftoversCorrupt("unknown encoding")),
+    };
+    // The encoder only writes records for non-zero tails, so an empty one is a cleared gap rather than a record
+    if decoded_len == 0 {
+        return Err(ConversionError::EmbeddedLeftoversCorrupt("no record in the fill gap"));
+    }
+    if tail.len() != decoded_len || decoded_len > tail_len {
+        return Err(ConversionError::EmbeddedLeftoversCorrupt("length does not match the header"));
+    }
+    if utils::crc32(&tail) != crc {
+        return Err(ConversionError::EmbeddedLeftoversCorrupt("CRC mismatch"));
+    }
+    tail.resize(tail_len, 0);
+    Ok(tail)
+}
+
+/// PackBits: a control byte n < 128 is followed by n + 1 literal bytes, n > 128 by one byte repeated 257 - n times
+fn packbits(data: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let run = data[i..].iter().take(128).take_while(|&&b| b == data[i]).count();
+        if run >= 2 {
+            out.push((257 - run) as u8);
+            out.push(data[i]);
+            i += run;
+            continue;
+        }
+        // Literals up to the next run of at least 2 equal bytes
+        let start = i;
+        while i < data.len() && i - start < 128 && !(i + 1 < data.len() && data[i] == data[i + 1]) {
+            i += 1;
+        }
+        out.push((i - start - 1) as u8);
+        out.extend_from_slice(&data[start..i]);
+    }
+    out
+}
+
+fn unpackbits(data: &[u8]) -> Option<Vec<u8>> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < data.len() {
+        let n = data[i] as usize;
+        i += 1;
+        match n {
+            0..=127 => {
+                out.extend_from_slice(data.get(i..i + n + 1)?);
+                i += n + 1;
+            }
+            128 => {}
+            _ => {
+                out.extend(std::iter::repeat_n(*data.get(i)?, 257 - n));
+                i += 1;
+            }
+        }
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CAPACITY: usize = 0x60;
+    const TAIL_LEN: usize = 0xA0;
+
+    /// Bytes that do not repeat, so PackBits cannot shrink them
+    fn noise(len: usize) -> Vec<u8> {
+        (0..len).map(|i| (i * 7 + 1) as u8).collect()
+    }
+
+    /// `record` written at the start of an otherwise zeroed fill gap
+    fn gap_with(record: &[u8]) -> Vec<u8> {
+        let mut gap = vec![0u8; 0x90];
+        gap[..record.len()].copy_from_slice(record);
+        gap
+    }
+
+    #[test]
+    fn packbits_round_trips() {
+        let mut mixed = noise(5);
+        mixed.extend([9; 300]);
+        mixed.extend(noise(200));
+        mixed.extend([0, 0, 1]);
+        for data in [vec![], vec![42], vec![7; 128], vec![7; 129], noise(128), noise(129), mixed] {
+            assert_eq!(unpackbits(&packbits(&data)), Some(data));
+        }
+    }
+
+    #[test]
+    fn packbits_shrinks_runs() {
+        assert_eq!(packbits(&[5; 128]), vec![129, 5]);
+        assert_eq!(packbits(&[1, 2, 3]), vec![2, 1, 2, 3]);
+    }
+
+    #[test]
+    fn unpackbits_rejects_truncated_input() {
+        assert_eq!(unpackbits(&[3, 1, 2]), None);
+        assert_eq!(unpackbits(&[200]), None);
+        assert_eq!(unpackbits(&[128]), Some(vec![]));
+    }
+
+    #[test]
+    fn record_round_trips_with_trailing_zeros_restored() {
+        let mut tail = noise(0x20);
+        tail.resize(TAIL_LEN, 0);
+        let record = encode(&tail, CAPACITY).expect("fits");
+        assert_eq!(record[0], ENCODING_RAW);
+        assert_eq!(decode(&gap_with(&record), TAIL_LEN), Ok(tail));
+    }
+
+    #[test]
+    fn compressible_tail_is_packed() {
+        let mut tail = vec![0xEE; 0x90];
+        tail.resize(TAIL_LEN, 0);
+        let record = encode(&tail, CAPACITY).expect("fits once packed");
+        assert_eq!(record[0], ENCODING_PACKBITS);
+        assert!(record.len() < CAPACITY);
+        assert_eq!(decode(&gap_with(&record), TAIL_LEN), Ok(tail));
+    }
+
+    #[test]
+    fn capacity_boundary() {
+        let fits = noise(CAPACITY - HEADER_LEN);
+        assert_eq!(encode(&fits, CAPACITY).map(|r| r.len()), Some(CAPACITY));
+        assert_eq!(encode(&noise(CAPACITY - HEADER_LEN + 1), CAPACITY), None);
+        assert_eq!(encode(&fits, CAPACITY - 1), None);
+    }
+
+    #[test]
+    fn truncated_gap_is_refused() {
+        let record = encode(&noise(0x20), CAPACITY).unwrap();
+        assert_eq!(decode(&record[..HEADER_LEN - 1], TAIL_LEN),
+                   Err(ConversionError::EmbeddedLeftoversCorrupt("fill gap too small for a record")));
+        assert_eq!(decode(&record[..record.len() - 1], TAIL_LEN),
+                   Err(ConversionError::EmbeddedLeftoversCorrupt("payload runs past the fill gap")));
+    }
+
+    #[test]
+    fn corrupt_record_is_refused() {
+        let record = encode(&noise(0x20), CAPACITY).unwrap();
+
+        let mut flipped = gap_with(&record);
+        flipped[HEADER_LEN + 3] ^= 0xFF;
+        assert_eq!(decode(&flipped, TAIL_LEN), Err(ConversionError::EmbeddedLeftoversCorrupt("CRC mismatch")));
+
+        let mut encoding = gap_with(&record);
+        encoding[0] = 7;
+        assert_eq!(decode(&encoding, TAIL_LEN), Err(ConversionError::EmbeddedLeftoversCorrupt("unknown encoding")));
+
+        let mut length = gap_with(&record);
+        length[1] += 1;
+        assert_eq!(decode(&length, TAIL_LEN),
+                   Err(ConversionError::EmbeddedLeftoversCorrupt("length does not match the header")));
+
+        assert_eq!(decode(&gap_with(&record), 0x10),
+                   Err(ConversionError::EmbeddedLeftoversCorrupt("length does not match the header")));
+    }
+
+    #[test]
+    fn vault_key_round_trips() {
+        let sha1 = "0123456789abcdef0123456789abcdef01234567";
+        let record = encode_vault_key(sha1);
+        assert_eq!(record, b"XV2K0123456789abcdef");
+        assert_eq!(read_vault_key(&gap_with(&record)), Some(sha1[..VAULT_KEY_LEN].to_string()));
+        assert_eq!(read_vault_key(b"XV2K0123456789ABCDEF"), Some("0123456789abcdef".to_string()));
+    }
+
+    #[test]
+    fn vault_key_needs_a_whole_hex_key() {
+        let record = encode_vault_key("0123456789abcdef0123456789abcdef01234567");
+        assert_eq!(read_vault_key(&[0u8; 0x90]), None);
+        assert_eq!(read_vault_key(&record[..record.len() - 1]), None);
+        assert_eq!(read_vault_key(b"XV2K0123456789abcdeg"), None);
+        let embedded = encode(&noise(0x20), CAPACITY).unwrap();
+        assert_eq!(read_vault_key(&gap_with(&embedded)), None);
+    }
+
+    #[test]
+    fn zeroed_gap_is_not_a_record() {
+        let gap = [0u8; 0x90];
+        assert_eq!(decode(&gap, 0xA0), Err(ConversionError::EmbeddedLeftoversCorrupt("no record in the fill gap")));
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt;
+
+/// Errors returned by the conversion API
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ConversionError {
+    /// Input is not the size the format requires
+    WrongSize { expected: usize, actual: usize },
+    /// '#SAV' magic not found where the layout requires it
+    MissingMagic { offset: usize },
+    /// Size and '#SAV' / marker positions match no known layout
+    NoMatchingLayout { size: usize, diagnosis: String },
+    /// No valid 8-byte marker at MARKER_OFFSET
+    UnrecognizedMarker,
+    /// Marker is valid but uses a version this build cannot unpack
+    UnsupportedVersion(u8),
+    /// Marker says v2/v3 but the rest of the file does not have their layout
+    MarkerLayoutMismatch,
+    /// Marker flags leftovers but none were supplied
+    LeftoversMissing,
+    /// Supplied leftovers do not cover the missing HCD tail
+    LeftoversShort { expected: usize, actual: usize },
+    /// The leftovers store failed to save or load data
+    LeftoversStorage(String),
+    /// Supplied leftovers are damaged or belong to another save
+    LeftoversMismatch(String),
+    /// The leftovers record in the fill gap of a v3 save is damaged
+    EmbeddedLeftoversCorrupt(&'static str),
+    /// Layout offsets cannot describe the data (negative or out of range segments)
+    InvalidLayout(&'static str),
+    /// Internal consistency check on an intermediate buffer failed
+    SizeMismatch { what: &'static str, expected: usize, actual: usize },
+}
+
+pub type Result<T> = std::result::Result<T, ConversionError>;
+
+impl ConversionError {
+    /// Stable snake_case name of the variant, for machine-readable output
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ConversionError::WrongSize { .. } => "wrong_size",
+            ConversionError::MissingMagic { .. } => "missing_magic",
+            ConversionError::NoMatchingLayout { .. } => "no_matching_layout",
+            ConversionError::UnrecognizedMarker => "unrecognized_marker",
+            ConversionError::UnsupportedVersion(_) => "unsupported_version",
+            ConversionError::MarkerLayoutMismatch => "marker_layout_mismatch",
+            ConversionError::LeftoversMissing => "leftovers_missing",
+            ConversionError::LeftoversShort { .. } => "leftovers_short",
+            ConversionError::LeftoversStorage(_) => "leftovers_storage",
+            ConversionError::LeftoversMismatch(_) => "leftovers_mismatch",
+            ConversionError::EmbeddedLeftoversCorrupt(_) => "embedded_leftovers_corrupt",
+            ConversionError::InvalidLayout(_) => "invalid_layout",
+            ConversionError::SizeMismatch { .. } => "size_mismatch",
+        }
+    }
+}
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConversionError::WrongSize { expected, actual } => {
+                write!(f, "Wrong save size: expected 0x{:X}, got 0x{:X}.", expected, actual)
+            }
+            ConversionError::MissingMagic { offset } => {
+                write!(f, "'#SAV' not found at 0x{:X}.", offset)
+            }
+            ConversionError::NoMatchingLayout { size, diagnosis } => {
+                write!(f, "Unknown format detected (0x{:X} bytes): {}.", size, diagnosis)
+            }
+            ConversionError::UnrecognizedMarker => {
+                write!(f, "Marker not recognized at 0x{:X}.", crate::constants::MARKER_OFFSET)
+            }
+            ConversionError::UnsupportedVersion(version) => {
+                write!(f, "Marker version 0x{:02X} is not supported.", version)
+            }
+            ConversionError::MarkerLayoutMismatch => {
+                write!(f, "Marker says v2/v3 but layout sanity checks failed.")
+            }
+            ConversionError::LeftoversMissing => {
+                write!(f, "Marker indicates leftovers, but no leftovers were supplied.")
+            }
+            ConversionError::LeftoversShort { expected, actual } => {
+                write!(f, "Leftovers too short: need 0x{:X} bytes, got 0x{:X}.", expected, actual)
+            }
+            ConversionError::LeftoversStorage(reason) => write!(f, "Leftovers store failed: {}.", reason),
+            ConversionError::LeftoversMismatch(reason) => write!(f, "Leftovers rejected: {}.", reason),
+            ConversionError::EmbeddedLeftoversCorrupt(reason) => {
+                write!(f, "Leftovers stored in the fill gap are damaged: {}.", reason)
+            }
+            ConversionError::InvalidLayout(reason) => write!(f, "Invalid layout: {}.", reason),
+            ConversionError::SizeMismatch { what, expected, actual } => {
+                write!(f, "Internal size mismatch: {} 0x{:X} != required 0x{:X}.", what, actual, expected)
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConversionError {}
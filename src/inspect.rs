@@ -0,0 +1,186 @@
+use crate::constants::{FLAG_LEFTOVERS, MAGIC, VER_V3};
+use crate::conversion::{self, LeftoversPolicy};
+use crate::detect::{self, SaveFormat};
+use crate::error::{ConversionError, Result};
+use crate::layout::SaveLayout;
+use crate::provenance::{self, ProvenanceCheck};
+use crate::{marker, utils};
+
+/// Description of a save, computed without converting it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SaveInfo {
+    pub format: SaveFormat,
+    pub layout: &'static SaveLayout,
+    pub size: usize,
+    /// Marker version byte (PC-ready only)
+    pub marker_version: Option<u8>,
+    /// Marker leftovers flag byte (PC-ready only)
+    pub leftovers_flag: Option<u8>,
+    pub z_byte: u8,
+    /// Every offset where '#SAV' appears
+    pub sav_offsets: Vec<usize>,
+    /// Fill gap between middle_segment and hcd_section in the PC-ready layout (embedded leftovers or vault key, provenance record, zeros)
+    pub fill_len: usize,
+    /// Bytes trimmed from the HCD tail when packing
+    pub excess: usize,
+    /// Zero bytes appended to the HCD tail when packing
+    pub pad: usize,
+    /// PS4: packing would produce leftovers. PC-ready: unpacking needs leftovers.
+    pub leftovers: bool,
+    /// PS4: packing stores the trimmed HCD tail in the fill gap. PC-ready: the fill gap holds it (marker v3).
+    pub embedded: bool,
+    pub sha1: String,
+    /// SHA1 of the converted save, when it does not depend on leftovers.
+    /// None without an error for PC-ready saves whose leftovers are kept outside: `inspect` does not read them, so the SHA1 is unknown.
+    pub converted_sha1: Option<String>,
+    /// Why the save could not be converted (PC-ready only), e.g. a damaged leftovers record; converted_sha1 is None then
+    pub conversion_error: Option<ConversionError>,
+    /// Whether the save changed since it was packed (PC-ready only)
+    pub provenance: Option<ProvenanceCheck>,
+}
+
+/// Detects `data` and describes it; the converted SHA1 is computed in memory when it does not need leftovers
+pub fn inspect(data: &[u8]) -> Result<SaveInfo> {
+    let detected = detect::detect(data)?;
+    let layout = detected.layout;
+    let geometry = conversion::pack_geometry(layout)?;
+
+    let provenance = match detected.format {
+        SaveFormat::Ps4 => None,
+        SaveFormat::PcReady => Some(provenance::check_provenance(data)?),
+    };
+    let mut conversion_error = None;
+    let (marker_version, leftovers_flag, z_byte, leftovers, embedded, converted_sha1) = match detected.format {
+        SaveFormat::Ps4 => {
+            let z_index = data.len() - 1;
+            let converted = conversion::ps4_to_pcready_with_layout(data, layout)?;
+            let leftovers = converted.leftovers.is_some();
+            (None, None, data[z_index], leftovers, converted.embedded, Some(utils::sha1_hex(&converted.data)))
+        }
+        SaveFormat::PcReady => {
+            let (version, flag) = marker::try_read_marker_at(data, layout.marker_offset)
+                .expect("detected PC-ready saves carry a marker");
+            let z_index = layout.pcready_sav_header_offset() - 1;
+            let needs_leftovers = flag == FLAG_LEFTOVERS;
+            // Strict, so a damaged embedded record is reported instead of hashing a zero-filled tail
+            let converted_sha1 = if needs_leftovers {
+                None
+            } else {
+                match conversion::convert_auto_with_layout(data, layout, None, LeftoversPolicy::Strict) {
+                    Ok(converted) => Some(utils::sha1_hex(&converted.data)),
+                    Err(e) => {
+                        conversion_error = Some(e);
+                        None
+                    }
+                }
+            };
+            (Some(version), Some(flag), data[z_index], needs_leftovers, version == VER_V3, converted_sha1)
+        }
+    };
+
+    Ok(SaveInfo {
+        format: detected.format,
+        layout,
+        size: data.len(),
+        marker_version,
+        leftovers_flag,
+        z_byte,
+        sav_offsets: find_magic(data),
+        fill_len: geometry.fill_len,
+        excess: geometry.excess,
+        pad: geometry.pad,
+        leftovers,
+        embedded,
+        sha1: utils::sha1_hex(data),
+        converted_sha1,
+        conversion_error,
+        provenance,
+    })
+}
+
+/// Offsets of every '#SAV' occurrence in `data`
+pub fn find_magic(data: &[u8]) -> Vec<usize> {
+    data.windows(MAGIC.len())
+        .enumerate()
+        .filter(|(_, w)| *w == MAGIC)
+        .map(|(i, _)| i)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::constants::{FLAG_NO_LEFTOVERS, VER_V2};
+    use crate::conversion::ps4_to_pcready;
+    use crate::layout::DEFAULT_LAYOUT;
+    use crate::test_support::{compressible_tail, random_tail, synthetic_ps4, TAIL_LEN};
+
+    #[test]
+    fn ps4_save() {
+        let ps4 = synthetic_ps4(&random_tail());
+        let info = inspect(&ps4).unwrap();
+        assert_eq!(info.format, SaveFormat::Ps4);
+        assert_eq!((info.size, info.z_byte), (DEFAULT_LAYOUT.ps4_size, 0x21));
+        assert_eq!((info.marker_version, info.leftovers_flag), (None, None));
+        assert_eq!(info.sav_offsets, vec![0x20, 0xA0]);
+        assert_eq!(info.excess, TAIL_LEN);
+        assert!(info.leftovers && !info.embedded);
+        assert_eq!(info.sha1, utils::sha1_hex(&ps4));
+        assert_eq!(info.converted_sha1, Some(utils::sha1_hex(&ps4_to_pcready(&ps4).unwrap().data)));
+        assert_eq!(info.provenance, None);
+    }
+
+    #[test]
+    fn pcready_save_without_leftovers() {
+        let ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        let info = inspect(&ps4_to_pcready(&ps4).unwrap().data).unwrap();
+        assert_eq!(info.format, SaveFormat::PcReady);
+        assert_eq!((info.marker_version, info.leftovers_flag), (Some(VER_V2), Some(FLAG_NO_LEFTOVERS)));
+        assert_eq!(info.sav_offsets, vec![0, DEFAULT_LAYOUT.pcready_sav_header_offset()]);
+        assert!(!info.leftovers && !info.embedded);
+        assert_eq!(info.converted_sha1, Some(utils::sha1_hex(&ps4)));
+        assert_eq!(info.conversion_error, None);
+    }
+
+    #[test]
+    fn pcready_save_with_embedded_leftovers() {
+        let ps4 = synthetic_ps4(&compressible_tail());
+        let info = inspect(&ps4_to_pcready(&ps4).unwrap().data).unwrap();
+        assert_eq!(info.marker_version, Some(VER_V3));
+        assert!(info.embedded);
+        assert_eq!(info.converted_sha1, Some(utils::sha1_hex(&ps4)));
+    }
+
+    #[test]
+    fn converted_sha1_is_unknown_when_the_leftovers_are_kept_outside() {
+        // The sidecar is never read, so its absence is not an error: the SHA1 just cannot be known
+        let packed = ps4_to_pcready(&synthetic_ps4(&random_tail())).unwrap();
+        assert!(packed.leftovers.is_some());
+        let info = inspect(&packed.data).unwrap();
+        assert_eq!(info.leftovers_flag, Some(FLAG_LEFTOVERS));
+        assert!(info.leftovers);
+        assert_eq!(info.converted_sha1, None);
+        assert_eq!(info.conversion_error, None);
+    }
+
+    #[test]
+    fn damaged_embedded_record_is_reported() {
+        let mut pcready = ps4_to_pcready(&synthetic_ps4(&compressible_tail())).unwrap().data;
+        let gap_start = DEFAULT_LAYOUT.hcd_start_pc_ready - conversion::pack_geometry(&DEFAULT_LAYOUT).unwrap().fill_len;
+        pcready[gap_start + 1] ^= 0xFF;
+        let info = inspect(&pcready).unwrap();
+        assert_eq!(info.converted_sha1, None);
+        assert!(matches!(info.conversion_error, Some(ConversionError::EmbeddedLeftoversCorrupt(_))), "{:?}", info.conversion_error);
+    }
+
+    #[test]
+    fn unrecognized_save_is_an_error() {
+        assert!(matches!(inspect(&[0; 0x100]), Err(ConversionError::NoMatchingLayout { .. })));
+    }
+
+    #[test]
+    fn find_magic_finds_every_occurrence() {
+        assert_eq!(find_magic(b"#SAV..#SAV#SA"), vec![0, 6]);
+        assert_eq!(find_magic(b"#SA"), Vec::<usize>::new());
+    }
+}
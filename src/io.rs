@@ -1,14 +1,162 @@
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use std::fs;
-use std::path::Path;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What to do when the output file already exists
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Overwrite {
+    /// Replace the existing file without keeping it
+    #[default]
+    Replace,
+    /// Keep a timestamped backup of the existing file first
+    Backup,
+    /// Fail instead of touching the existing file
+    Refuse,
+}
+
+/// Earlier version of a file, kept as `<file>.bak-<stamp>`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    /// UTC time the backup was taken, `YYYYMMDD-HHMMSS` (plus `-N` if several were taken that second)
+    pub stamp: String,
+}
 
 pub fn read_file_bytes<P: AsRef<Path>>(path: P) -> Result<Vec<u8>> {
     let data = fs::read(path)?;
     Ok(data)
 }
 
+/// Writes `data` to `path` atomically, replacing an existing file
 pub fn write_output_file<P: AsRef<Path>>(path: P, data: &[u8]) -> Result<()> {
-    fs::write(path, data)
-        .with_context(|| "Failed to write output file".to_string())?;
+    write_output_file_with(path, data, Overwrite::Replace)?;
     Ok(())
-}
\ No newline at end of file
+}
+
+/// Writes `data` to `path` atomically; returns the backup taken of the previous file, if any
+pub fn write_output_file_with<P: AsRef<Path>>(path: P, data: &[u8], overwrite: Overwrite) -> Result<Option<Backup>> {
+    let path = path.as_ref();
+    let backup = match overwrite {
+        Overwrite::Refuse if path.exists() => {
+            bail!("Refusing to overwrite existing file: {}", path.display());
+        }
+        Overwrite::Refuse | Overwrite::Replace => None,
+        Overwrite::Backup => {
+            let stamp = backup_stamp(&[path]);
+            backup_existing(path, &stamp)
+                .with_context(|| format!("Failed to back up {}", path.display()))?
+        }
+    };
+    write_atomic(path, data)
+        .with_context(|| "Failed to write output file".to_string())?;
+    Ok(backup)
+}
+
+/// Writes `data` to a temporary file next to `path`, then renames it over `path`,
+/// so readers see either the old or the new content, never a partial file
+pub fn write_atomic<P: AsRef<Path>>(path: P, data: &[u8]) -> io::Result<()> {
+    let path = path.as_ref();
+    let mut tmp_name = path.file_name().unwrap_or_default().to_os_string();
+    tmp_name.push(format!(".tmp-{}", std::process::id()));
+    let tmp = path.with_file_name(tmp_name);
+
+    let written = (|| {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(data)?;
+        file.sync_all()?;
+        fs::rename(&tmp, path)
+    })();
+    if written.is_err() {
+        let _ = fs::remove_file(&tmp);
+    }
+    written
+}
+
+/// Current UTC time as a backup stamp no backup of `paths` uses yet,
+/// so files backed up together (a save and its sidecar) share one stamp
+pub fn backup_stamp(paths: &[&Path]) -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+    let base = format_utc(secs);
+    let mut stamp = base.clone();
+    let mut n = 2;
+    while paths.iter().any(|p| backup_path(p, &stamp).exists()) {
+        stamp = format!("{}-{}", base, n);
+        n += 1;
+    }
+    stamp
+}
+
+/// Keeps `path` as `<path>.bak-<stamp>` if it exists. The backup is a hard link (a copy where links are not
+/// supported), so `path` itself stays in place until a write_atomic rename replaces it.
+pub fn backup_existing<P: AsRef<Path>>(path: P, stamp: &str) -> io::Result<Option<Backup>> {
+    let path = path.as_ref();
+    if !path.exists() {
+        return Ok(None);
+    }
+    let backup = backup_path(path, stamp);
+    if fs::hard_link(path, &backup).is_err() {
+        fs::copy(path, &backup)?;
+    }
+    Ok(Some(Backup { path: backup, stamp: stamp.to_string() }))
+}
+
+/// Backups of `path`, newest first
+pub fn list_backups<P: AsRef<Path>>(path: P) -> io::Result<Vec<Backup>> {
+    let path = path.as_ref();
+    let prefix = format!("{}.bak-", path.file_name().unwrap_or_default().to_string_lossy());
+    let dir = match path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        Some(dir) => dir.to_path_buf(),
+        None => PathBuf::from("."),
+    };
+
+    let mut backups = Vec::new();
+    let entries = match fs::read_dir(&dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(backups),
+        Err(e) => return Err(e),
+    };
+    for entry in entries {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        if let Some(stamp) = name.strip_prefix(&prefix) {
+            backups.push(Backup { path: backup_path(path, stamp), stamp: stamp.to_string() });
+        }
+    }
+    // Stamps sort by time; a `-N` suffix sorts after the plain stamp of the same second
+    backups.sort_by_key(|b| std::cmp::Reverse(stamp_order(&b.stamp)));
+    Ok(backups)
+}
+
+/// `<path>.bak-<stamp>`
+pub fn backup_path<P: AsRef<Path>>(path: P, stamp: &str) -> PathBuf {
+    let mut name = path.as_ref().as_os_str().to_os_string();
+    name.push(format!(".bak-{}", stamp));
+    PathBuf::from(name)
+}
+
+fn stamp_order(stamp: &str) -> (String, u32) {
+    match stamp.get(15..).and_then(|s| s.strip_prefix('-')).and_then(|n| n.parse().ok()) {
+        Some(n) => (stamp[..15].to_string(), n),
+        None => (stamp.to_string(), 1),
+    }
+}
+
+/// Seconds since the Unix epoch as `YYYYMMDD-HHMMSS` (UTC)
+fn format_utc(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let rem = secs % 86_400;
+
+    // Civil date from days since 1970-01-01 (Howard Hinnant's algorithm)
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+
+    format!("{:04}{:02}{:02}-{:02}{:02}{:02}", year, month, day, rem / 3600, rem / 60 % 60, rem % 60)
+}
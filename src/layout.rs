@@ -0,0 +1,93 @@
+use crate::constants::*;
+use crate::error::{ConversionError, Result};
+
+/// Sizes and offsets describing the save layout of one game version.
+/// Conversions do all of their arithmetic on these values, so a new game version only needs a new entry in KNOWN_LAYOUTS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SaveLayout {
+    /// Short identifier, e.g. for CLI output
+    pub name: &'static str,
+    /// Total size of the PS4 save file (includes the MD5 header)
+    pub ps4_size: usize,
+    /// Total size of the PC-ready save file
+    pub editor_size: usize,
+    /// Size of the MD5 header at the start of the PS4 save
+    pub md5_header_size: usize,
+    /// Size of the #SAV section following the MD5 header
+    pub sav_header_size: usize,
+    /// Offset of the 8-byte format marker in the PC-ready save
+    pub marker_offset: usize,
+    /// Hero Coliseum Data start in PS4 format (counted from the start of [SAV_HEADER])
+    pub hcd_start_ps4: usize,
+    /// Where PC-ready expects HCD start (absolute offset in the converted file)
+    pub hcd_start_pc_ready: usize,
+}
+
+/// Layout the converter was written against
+pub const DEFAULT_LAYOUT: SaveLayout = SaveLayout {
+    name: "default",
+    ps4_size: PS4_SIZE,
+    editor_size: EDITOR_SIZE,
+    md5_header_size: MD5_HEADER_SIZE,
+    sav_header_size: SAV_HEADER_SIZE,
+    marker_offset: MARKER_OFFSET,
+    hcd_start_ps4: HCD_START_PS4,
+    hcd_start_pc_ready: HCD_START_PC_READY,
+};
+
+/// Every layout the converter knows about, most recent first.
+/// Only DEFAULT_LAYOUT so far: no other game version's sizes and offsets have been measured from real saves.
+pub const KNOWN_LAYOUTS: &[SaveLayout] = &[DEFAULT_LAYOUT];
+
+impl Default for SaveLayout {
+    fn default() -> Self {
+        DEFAULT_LAYOUT
+    }
+}
+
+impl SaveLayout {
+    /// Looks a layout up in KNOWN_LAYOUTS by name
+    pub fn by_name(name: &str) -> Option<&'static SaveLayout> {
+        KNOWN_LAYOUTS.iter().find(|l| l.name == name)
+    }
+
+    /// Checks that the offsets are consistent with each other, so the helpers below cannot underflow
+    pub fn validate(&self) -> Result<()> {
+        let headers = self.md5_header_size + self.sav_header_size + 1;
+        if self.ps4_size <= headers || self.editor_size <= headers {
+            return Err(ConversionError::InvalidLayout("save sizes smaller than the headers"));
+        }
+        if self.hcd_start_ps4 < self.sav_header_size + self.marker_offset {
+            return Err(ConversionError::InvalidLayout("bad Hcd1 start offset (middle_segment would be negative)"));
+        }
+        if self.hcd_start_in_middle() >= self.middle_len() {
+            return Err(ConversionError::InvalidLayout("bad Hcd1 start offset (hcd_section empty)"));
+        }
+        if self.hcd_start_pc_ready < self.marker_offset + MARKER_LEN
+            || self.hcd_start_pc_ready > self.pcready_sav_header_offset() - 1
+        {
+            return Err(ConversionError::InvalidLayout("hcd_section start beyond z_byte"));
+        }
+        Ok(())
+    }
+
+    /// Offsets of the two '#SAV' magics in the PS4 save (0x20 and 0xA0 in the default layout)
+    pub fn ps4_magic_offsets(&self) -> [usize; 2] {
+        [self.md5_header_size, self.md5_header_size + self.sav_header_size]
+    }
+
+    /// Offset of [SAV_HEADER] in the PC-ready save (right before the trailing MD5 header)
+    pub fn pcready_sav_header_offset(&self) -> usize {
+        self.editor_size - self.md5_header_size - self.sav_header_size
+    }
+
+    /// Length of the PS4 middle part: everything between [SAV_HEADER] and the Z byte
+    pub fn middle_len(&self) -> usize {
+        self.ps4_size - self.md5_header_size - self.sav_header_size - 1
+    }
+
+    /// Position of hcd_section within the PS4 middle part
+    pub fn hcd_start_in_middle(&self) -> usize {
+        self.hcd_start_ps4 - self.sav_header_size
+    }
+}
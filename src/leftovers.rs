@@ -0,0 +1,444 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::{ConversionError, Result};
+use crate::io::write_atomic;
+use crate::utils;
+
+/// First bytes of a leftovers file; files without them are legacy raw leftovers
+const FILE_MAGIC: [u8; 8] = *b"XV2LEFT\0";
+const FILE_VERSION: u8 = 1;
+/// magic, version + 3 reserved, three SHA1s, hcd_offset, length, CRC-32
+const FILE_HEADER_LEN: usize = 8 + 4 + 3 * 20 + 4 + 4 + 4;
+
+/// What a leftovers file says about the save it belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeftoversHeader {
+    /// SHA1 of the PS4 save the leftovers were trimmed from
+    pub ps4_sha1: String,
+    /// SHA1 of the PC-ready save produced from it
+    pub pcready_sha1: String,
+    /// SHA1 of that PS4 save's MD5 header, which the PC-ready save keeps unchanged while it is edited
+    pub origin_sha1: String,
+    /// Offset in the PS4 save where the leftovers belong
+    pub hcd_offset: usize,
+    pub length: usize,
+    /// CRC-32 of the leftovers
+    pub crc32: u32,
+}
+
+/// Leftovers file contents: a header identifying the save, then the leftovers
+pub fn encode_leftovers(header: &LeftoversHeader, leftovers: &[u8]) -> Vec<u8> {
+    let mut file = Vec::with_capacity(FILE_HEADER_LEN + leftovers.len());
+    file.extend_from_slice(&FILE_MAGIC);
+    file.extend_from_slice(&[FILE_VERSION, 0, 0, 0]);
+    for sha1 in [&header.ps4_sha1, &header.pcready_sha1, &header.origin_sha1] {
+        file.extend_from_slice(&sha1_bytes(sha1));
+    }
+    file.extend_from_slice(&(header.hcd_offset as u32).to_le_bytes());
+    file.extend_from_slice(&(leftovers.len() as u32).to_le_bytes());
+    file.extend_from_slice(&utils::crc32(leftovers).to_le_bytes());
+    file.extend_from_slice(leftovers);
+    file
+}
+
+/// Splits a leftovers file into its header and the leftovers, checking length and CRC.
+/// `None` for a legacy file of raw leftovers without a header.
+pub fn parse_leftovers(file: &[u8]) -> Result<Option<(LeftoversHeader, &[u8])>> {
+    if !file.starts_with(&FILE_MAGIC) {
+        return Ok(None);
+    }
+    if file.len() < FILE_HEADER_LEN {
+        return Err(ConversionError::LeftoversMismatch("leftovers file header is cut short".to_string()));
+    }
+    if file[8] != FILE_VERSION {
+        return Err(ConversionError::LeftoversMismatch(format!("unknown leftovers file version {}", file[8])));
+    }
+    let u32_at = |o: usize| u32::from_le_bytes([file[o], file[o + 1], file[o + 2], file[o + 3]]);
+    let header = LeftoversHeader {
+        ps4_sha1: utils::to_hex(&file[12..32]),
+        pcready_sha1: utils::to_hex(&file[32..52]),
+        origin_sha1: utils::to_hex(&file[52..72]),
+        hcd_offset: u32_at(72) as usize,
+        length: u32_at(76) as usize,
+        crc32: u32_at(80),
+    };
+    let leftovers = &file[FILE_HEADER_LEN..];
+    if leftovers.len() != header.length {
+        return Err(ConversionError::LeftoversMismatch(format!(
+            "leftovers file holds 0x{:X} bytes but its header says 0x{:X}", leftovers.len(), header.length
+        )));
+    }
+    if utils::crc32(leftovers) != header.crc32 {
+        return Err(ConversionError::LeftoversMismatch("leftovers file is damaged (CRC mismatch)".to_string()));
+    }
+    Ok(Some((header, leftovers)))
+}
+
+fn sha1_bytes(hex: &str) -> [u8; 20] {
+    let mut bytes = [0u8; 20];
+    for (i, b) in bytes.iter_mut().enumerate() {
+        *b = hex.get(2 * i..2 * i + 2).and_then(|h| u8::from_str_radix(h, 16).ok()).unwrap_or(0);
+    }
+    bytes
+}
+
+/// Identifies the save a set of leftovers belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LeftoversKey {
+    /// SHA1 of the PC-ready save
+    pub pcready_sha1: String,
+    /// SHA1 of the original PS4 save, known while packing but not while unpacking
+    pub ps4_sha1: Option<String>,
+    /// Vault key kept in the fill gap of the PC-ready save, which survives editing it
+    pub vault_key: Option<String>,
+}
+
+/// Where the HCD tail bytes removed by `ps4_to_pcready` are kept until `pcready_to_ps4` needs them
+pub trait LeftoversStore {
+    /// Keeps `leftovers` for the save identified by `key`
+    fn store(&mut self, key: &LeftoversKey, leftovers: &[u8]) -> io::Result<()>;
+
+    /// Returns the leftovers for `key`, or `None` if this store has none
+    fn load(&self, key: &LeftoversKey) -> io::Result<Option<Vec<u8>>>;
+
+    /// Human-readable location of the leftovers for `key`, for messages
+    fn describe(&self, key: &LeftoversKey) -> String;
+}
+
+/// Single sidecar file, by default `<pcready>.leftovers.dec` next to the PC-ready save
+pub struct SidecarStore {
+    path: PathBuf,
+}
+
+impl SidecarStore {
+    pub fn new<P: AsRef<Path>>(path: P) -> Self {
+        SidecarStore { path: path.as_ref().to_path_buf() }
+    }
+
+    /// Sidecar next to the PC-ready save at `pcready_path`
+    pub fn for_pcready<P: AsRef<Path>>(pcready_path: P) -> Self {
+        let mut path = pcready_path.as_ref().as_os_str().to_os_string();
+        path.push(".leftovers.dec");
+        SidecarStore { path: PathBuf::from(path) }
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl LeftoversStore for SidecarStore {
+    fn store(&mut self, _key: &LeftoversKey, leftovers: &[u8]) -> io::Result<()> {
+        write_atomic(&self.path, leftovers)
+    }
+
+    fn load(&self, _key: &LeftoversKey) -> io::Result<Option<Vec<u8>>> {
+        read_if_exists(&self.path)
+    }
+
+    fn describe(&self, _key: &LeftoversKey) -> String {
+        self.path.to_string_lossy().to_string()
+    }
+}
+
+/// In-memory store keyed by the PC-ready SHA1
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: HashMap<String, Vec<u8>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl LeftoversStore for MemoryStore {
+    fn store(&mut self, key: &LeftoversKey, leftovers: &[u8]) -> io::Result<()> {
+        self.entries.insert(key.pcready_sha1.clone(), leftovers.to_vec());
+        Ok(())
+    }
+
+    fn load(&self, key: &LeftoversKey) -> io::Result<Option<Vec<u8>>> {
+        Ok(self.entries.get(&key.pcready_sha1).cloned())
+    }
+
+    fn describe(&self, key: &LeftoversKey) -> String {
+        format!("memory:{}", key.pcready_sha1)
+    }
+}
+
+/// Directory of leftovers keyed by the PS4 SHA1, so renamed or moved saves still find theirs.
+/// Layout: `<root>/<ps4_sha1>.leftovers` holds the bytes, `<root>/<pcready_sha1>.ref` names the PS4 SHA1.
+/// Saves carrying a vault key in their fill gap also get `<root>/<vault_key>.ref`, which still finds them after editing.
+pub struct ContentAddressedStore {
+    root: PathBuf,
+    address: Address,
+}
+
+/// Which SHA1 names the leftovers files of a ContentAddressedStore
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Address {
+    Ps4Sha1,
+    PcreadySha1,
+}
+
+impl ContentAddressedStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        ContentAddressedStore { root: root.as_ref().to_path_buf(), address: Address::Ps4Sha1 }
+    }
+
+    /// Store whose leftovers files are named by the PC-ready SHA1 instead (the vault layout)
+    fn by_pcready_sha1<P: AsRef<Path>>(root: P) -> Self {
+        ContentAddressedStore { root: root.as_ref().to_path_buf(), address: Address::PcreadySha1 }
+    }
+
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    fn data_path(&self, name: &str) -> PathBuf {
+        self.root.join(format!("{}.leftovers", name))
+    }
+
+    fn ref_path(&self, alias: &str) -> PathBuf {
+        self.root.join(format!("{}.ref", alias))
+    }
+
+    /// Name the leftovers file for `key` gets, if the key says
+    fn name<'k>(&self, key: &'k LeftoversKey) -> Option<&'k str> {
+        match self.address {
+            Address::Ps4Sha1 => key.ps4_sha1.as_deref(),
+            Address::PcreadySha1 => Some(&key.pcready_sha1),
+        }
+    }
+
+    /// Other names of the save, each with a `.ref` file pointing to the leftovers file
+    fn aliases<'k>(&self, key: &'k LeftoversKey) -> Vec<&'k str> {
+        let pcready = Some(key.pcready_sha1.as_str()).filter(|_| self.address != Address::PcreadySha1);
+        pcready.into_iter().chain(key.vault_key.as_deref()).collect()
+    }
+
+    /// Name of the leftovers file stored for `key`, if the store has one
+    fn resolve(&self, key: &LeftoversKey) -> io::Result<Option<String>> {
+        if let Some(name) = self.name(key).filter(|name| self.data_path(name).is_file()) {
+            return Ok(Some(name.to_string()));
+        }
+        for alias in self.aliases(key) {
+            if let Some(r) = read_if_exists(&self.ref_path(alias))? {
+                // The name becomes a path under root, so anything but a SHA1 (e.g. "../x") is refused
+                let name = String::from_utf8_lossy(&r).trim().to_string();
+                if name.len() != 40 || !name.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+                    return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                        "{} does not name a leftovers file (expected a 40-digit lowercase SHA1)", self.ref_path(alias).display()
+                    )));
+                }
+                return Ok(Some(name));
+            }
+        }
+        Ok(None)
+    }
+}
+
+impl LeftoversStore for ContentAddressedStore {
+    fn store(&mut self, key: &LeftoversKey, leftovers: &[u8]) -> io::Result<()> {
+        let name = self.name(key).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "content-addressed store needs the PS4 SHA1")
+        })?;
+        fs::create_dir_all(&self.root)?;
+        write_atomic(self.data_path(name), leftovers)?;
+        for alias in self.aliases(key) {
+            write_atomic(self.ref_path(alias), name.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn load(&self, key: &LeftoversKey) -> io::Result<Option<Vec<u8>>> {
+        match self.resolve(key)? {
+            Some(name) => read_if_exists(&self.data_path(&name)),
+            None => Ok(None),
+        }
+    }
+
+    fn describe(&self, key: &LeftoversKey) -> String {
+        let path = match (self.resolve(key), self.name(key)) {
+            (Ok(Some(name)), _) => self.data_path(&name),
+            (_, Some(name)) => self.data_path(name),
+            _ => self.ref_path(&key.pcready_sha1),
+        };
+        path.to_string_lossy().to_string()
+    }
+}
+
+/// Leftovers vault shared by every conversion, so a save finds its leftovers wherever it was moved or renamed.
+/// A ContentAddressedStore whose leftovers files are named by the PC-ready SHA1, found through the vault key for edited saves.
+pub struct VaultStore {
+    inner: ContentAddressedStore,
+}
+
+impl VaultStore {
+    pub fn new<P: AsRef<Path>>(root: P) -> Self {
+        VaultStore { inner: ContentAddressedStore::by_pcready_sha1(root) }
+    }
+
+    /// `$XDG_DATA_HOME/xv2-converter/leftovers`, else under `~/.local/share` (`%APPDATA%` on Windows)
+    pub fn default_root() -> Option<PathBuf> {
+        let non_empty = |name| std::env::var_os(name).filter(|v| !v.is_empty()).map(PathBuf::from);
+        let data_home = non_empty("XDG_DATA_HOME")
+            .or_else(|| non_empty("HOME").map(|home| home.join(".local").join("share")))
+            .or_else(|| non_empty("APPDATA"))?;
+        Some(data_home.join("xv2-converter").join("leftovers"))
+    }
+
+    pub fn root(&self) -> &Path {
+        self.inner.root()
+    }
+}
+
+impl LeftoversStore for VaultStore {
+    fn store(&mut self, key: &LeftoversKey, leftovers: &[u8]) -> io::Result<()> {
+        self.inner.store(key, leftovers)
+    }
+
+    fn load(&self, key: &LeftoversKey) -> io::Result<Option<Vec<u8>>> {
+        self.inner.load(key)
+    }
+
+    fn describe(&self, key: &LeftoversKey) -> String {
+        self.inner.describe(key)
+    }
+}
+
+fn read_if_exists(path: &Path) -> io::Result<Option<Vec<u8>>> {
+    match fs::read(path) {
+        Ok(data) => Ok(Some(data)),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn header(leftovers: &[u8]) -> LeftoversHeader {
+        LeftoversHeader {
+            ps4_sha1: "11".repeat(20),
+            pcready_sha1: "22".repeat(20),
+            origin_sha1: "ab".repeat(20),
+            hcd_offset: 0x12A15F,
+            length: leftovers.len(),
+            crc32: utils::crc32(leftovers),
+        }
+    }
+
+    #[test]
+    fn file_round_trips() {
+        let leftovers = [1, 2, 3, 0, 5];
+        let file = encode_leftovers(&header(&leftovers), &leftovers);
+        assert_eq!(file.len(), FILE_HEADER_LEN + leftovers.len());
+        assert_eq!(parse_leftovers(&file), Ok(Some((header(&leftovers), &leftovers[..]))));
+    }
+
+    #[test]
+    fn raw_file_is_legacy() {
+        assert_eq!(parse_leftovers(&[0xAA; 0xA0]), Ok(None));
+        assert_eq!(parse_leftovers(&[]), Ok(None));
+    }
+
+    #[test]
+    fn truncated_header_is_refused() {
+        let file = encode_leftovers(&header(&[7; 4]), &[7; 4]);
+        assert!(matches!(parse_leftovers(&file[..FILE_HEADER_LEN - 1]), Err(ConversionError::LeftoversMismatch(_))));
+    }
+
+    #[test]
+    fn unknown_version_is_refused() {
+        let mut file = encode_leftovers(&header(&[7; 4]), &[7; 4]);
+        file[8] = FILE_VERSION + 1;
+        assert!(matches!(parse_leftovers(&file), Err(ConversionError::LeftoversMismatch(m)) if m.contains("version")));
+    }
+
+    #[test]
+    fn truncated_leftovers_are_refused() {
+        let file = encode_leftovers(&header(&[7; 4]), &[7; 4]);
+        assert!(matches!(parse_leftovers(&file[..file.len() - 1]), Err(ConversionError::LeftoversMismatch(m)) if m.contains("header says")));
+    }
+
+    #[test]
+    fn corrupt_leftovers_are_refused() {
+        let mut file = encode_leftovers(&header(&[7; 4]), &[7; 4]);
+        *file.last_mut().unwrap() ^= 0xFF;
+        assert!(matches!(parse_leftovers(&file), Err(ConversionError::LeftoversMismatch(m)) if m.contains("CRC")));
+    }
+
+    /// Empty directory under the system temp dir, unique to `name` and this process
+    fn temp_root(name: &str) -> PathBuf {
+        let root = std::env::temp_dir().join(format!("xv2-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        root
+    }
+
+    fn key(pcready_sha1: &str, vault_key: Option<&str>) -> LeftoversKey {
+        LeftoversKey {
+            pcready_sha1: pcready_sha1.to_string(),
+            ps4_sha1: Some("11".repeat(20)),
+            vault_key: vault_key.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn vault_finds_leftovers_by_sha1_and_by_vault_key() {
+        let root = temp_root("vault");
+        let mut vault = VaultStore::new(&root);
+        vault.store(&key(&"aa".repeat(20), Some("1111111111111111")), &[1, 2, 3]).unwrap();
+        assert!(root.join(format!("{}.leftovers", "aa".repeat(20))).is_file());
+
+        let unpacking = LeftoversKey { ps4_sha1: None, ..key(&"aa".repeat(20), None) };
+        assert_eq!(vault.load(&unpacking).unwrap(), Some(vec![1, 2, 3]));
+        // Edited since: another SHA1, but the same vault key in its fill gap
+        let edited = LeftoversKey { ps4_sha1: None, ..key(&"bb".repeat(20), Some("1111111111111111")) };
+        assert_eq!(vault.load(&edited).unwrap(), Some(vec![1, 2, 3]));
+        assert_eq!(vault.describe(&edited), root.join(format!("{}.leftovers", "aa".repeat(20))).to_string_lossy());
+        let other = LeftoversKey { ps4_sha1: None, ..key(&"cc".repeat(20), Some("2222222222222222")) };
+        assert_eq!(vault.load(&other).unwrap(), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn content_addressed_store_finds_leftovers_by_vault_key() {
+        let root = temp_root("cas");
+        let mut store = ContentAddressedStore::new(&root);
+        store.store(&key(&"aa".repeat(20), Some("1111111111111111")), &[4, 5]).unwrap();
+        assert!(root.join(format!("{}.leftovers", "11".repeat(20))).is_file());
+
+        let by_sha1 = LeftoversKey { ps4_sha1: None, ..key(&"aa".repeat(20), None) };
+        assert_eq!(store.load(&by_sha1).unwrap(), Some(vec![4, 5]));
+        let edited = LeftoversKey { ps4_sha1: None, ..key(&"bb".repeat(20), Some("1111111111111111")) };
+        assert_eq!(store.load(&edited).unwrap(), Some(vec![4, 5]));
+        let unknown = LeftoversKey { ps4_sha1: None, ..key(&"cc".repeat(20), None) };
+        assert_eq!(store.load(&unknown).unwrap(), None);
+        fs::remove_dir_all(&root).unwrap();
+    }
+
+    #[test]
+    fn ref_naming_anything_but_a_sha1_is_refused() {
+        let root = temp_root("refs");
+        let mut store = ContentAddressedStore::new(&root);
+        store.store(&key(&"aa".repeat(20), None), &[4, 5]).unwrap();
+        fs::write(root.join("x.leftovers"), [6]).unwrap();
+        let by_sha1 = LeftoversKey { ps4_sha1: None, ..key(&"aa".repeat(20), None) };
+        for bad in ["../x", "x", &"AB".repeat(20), &"1".repeat(41)] {
+            fs::write(store.ref_path(&"aa".repeat(20)), bad).unwrap();
+            let err = store.load(&by_sha1).unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::InvalidData, "{bad}");
+        }
+        // Trailing whitespace from a hand-edited ref is fine
+        fs::write(store.ref_path(&"aa".repeat(20)), format!("{}\n", "11".repeat(20))).unwrap();
+        assert_eq!(store.load(&by_sha1).unwrap(), Some(vec![4, 5]));
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
@@ -6,28 +6,105 @@ mod utils;
 mod io;
 mod marker;
 mod conversion;
+mod detect;
+mod embedded;
+mod doctor;
+mod error;
+mod inspect;
+mod layout;
+mod leftovers;
+mod md5_header;
+mod provenance;
+mod verify;
+#[cfg(test)]
+mod test_support;
 
 pub use constants::*;
-pub use utils::sha1_hex;
-pub use io::write_output_file;
-pub use marker::{has_dual_magic, has_any_marker_at_08, has_magic_at, make_marker, try_read_marker, looks_like_v2};
+pub use utils::{sha1_hex, to_hex};
+pub use io::{
+    backup_existing, backup_path, backup_stamp, list_backups, read_file_bytes, write_atomic,
+    write_output_file, write_output_file_with, Backup, Overwrite,
+};
+pub use marker::{has_dual_magic, has_any_marker_at_08, has_magic_at, make_marker, try_read_marker, try_read_marker_at, looks_like_v2, looks_like_v2_in};
+pub use conversion::{leftovers_key, pack_geometry, ConversionOutput, LeftoversPolicy, PackGeometry};
+pub use detect::{detect, Detection, SaveFormat};
+pub use doctor::{doctor, Check, Diagnosis, Finding};
+pub use error::ConversionError;
+pub use inspect::{inspect, find_magic, SaveInfo};
+pub use layout::{SaveLayout, DEFAULT_LAYOUT, KNOWN_LAYOUTS};
+pub use leftovers::{encode_leftovers, parse_leftovers, LeftoversHeader, LeftoversKey, LeftoversStore, SidecarStore, MemoryStore, ContentAddressedStore, VaultStore};
+pub use md5_header::{check_md5_header, check_md5_header_as, Md5Encoding, Md5Status};
+pub use provenance::{check_provenance, Provenance, ProvenanceCheck, ProvenanceStatus};
+pub use verify::{verify_round_trip, diff_regions, region_at, DiffRange, Region, RoundTripReport};
 
 #[cfg(feature = "python")]
 use pyo3::prelude::*;
 
 /// Converts a PS4 save file to PC-ready format
-pub fn ps4_to_pcready(data: &[u8], input_path: &str, dir: &str) -> Result<Vec<u8>, anyhow::Error> {
-    conversion::ps4_to_pcready(data, input_path, dir)
+/// Any non-zero HCD tail bytes that had to be removed are returned in `leftovers`, as the contents of a leftovers file
+pub fn ps4_to_pcready(data: &[u8]) -> Result<ConversionOutput, ConversionError> {
+    conversion::ps4_to_pcready(data)
 }
 
 /// Converts a PC-ready save file to PS4 format
-pub fn pcready_to_ps4(data: &[u8], input_path: &str, dir: &str, has_leftovers_flag: bool) -> Result<Vec<u8>, anyhow::Error> {
-    conversion::pcready_to_ps4(data, input_path, dir, has_leftovers_flag)
+/// `leftovers` are the bytes returned by a previous `ps4_to_pcready` call, if any; missing ones are zero-filled
+pub fn pcready_to_ps4(data: &[u8], has_leftovers_flag: bool, leftovers: Option<&[u8]>) -> Result<ConversionOutput, ConversionError> {
+    conversion::pcready_to_ps4(data, has_leftovers_flag, leftovers, LeftoversPolicy::default())
+}
+
+/// `pcready_to_ps4` with `policy` deciding what happens when flagged leftovers are missing or too short
+pub fn pcready_to_ps4_with_policy(data: &[u8], has_leftovers_flag: bool, leftovers: Option<&[u8]>, policy: LeftoversPolicy) -> Result<ConversionOutput, ConversionError> {
+    conversion::pcready_to_ps4(data, has_leftovers_flag, leftovers, policy)
 }
 
 /// Automatically detects the format and converts accordingly
-pub fn convert_auto(data: &[u8], input_path: &str, dir: &str) -> Result<Vec<u8>, anyhow::Error> {
-    conversion::convert_auto(data, input_path, dir)
+pub fn convert_auto(data: &[u8], leftovers: Option<&[u8]>) -> Result<ConversionOutput, ConversionError> {
+    conversion::convert_auto(data, leftovers, LeftoversPolicy::default())
+}
+
+/// `convert_auto` with `policy` deciding what happens when flagged leftovers are missing or too short
+pub fn convert_auto_with_policy(data: &[u8], leftovers: Option<&[u8]>, policy: LeftoversPolicy) -> Result<ConversionOutput, ConversionError> {
+    conversion::convert_auto(data, leftovers, policy)
+}
+
+/// Converts a PS4 save file with the given layout to PC-ready format
+pub fn ps4_to_pcready_with_layout(data: &[u8], layout: &SaveLayout) -> Result<ConversionOutput, ConversionError> {
+    conversion::ps4_to_pcready_with_layout(data, layout)
+}
+
+/// Converts a PC-ready save file with the given layout to PS4 format
+pub fn pcready_to_ps4_with_layout(data: &[u8], layout: &SaveLayout, has_leftovers_flag: bool, leftovers: Option<&[u8]>) -> Result<ConversionOutput, ConversionError> {
+    conversion::pcready_to_ps4_with_layout(data, layout, has_leftovers_flag, leftovers, LeftoversPolicy::default())
+}
+
+/// Converts a PC-ready save file with the given layout according to its marker
+pub fn convert_auto_with_layout(data: &[u8], layout: &SaveLayout, leftovers: Option<&[u8]>) -> Result<ConversionOutput, ConversionError> {
+    conversion::convert_auto_with_layout(data, layout, leftovers, LeftoversPolicy::default())
+}
+
+/// Converts a PS4 save file to PC-ready format, keeping any leftovers in `store`
+pub fn ps4_to_pcready_with_store(data: &[u8], store: &mut dyn LeftoversStore) -> Result<ConversionOutput, ConversionError> {
+    conversion::ps4_to_pcready_with_store(data, store)
+}
+
+/// Converts a PC-ready save file to PS4 format, looking leftovers up in `store`
+pub fn pcready_to_ps4_with_store(data: &[u8], has_leftovers_flag: bool, store: &mut dyn LeftoversStore) -> Result<ConversionOutput, ConversionError> {
+    conversion::pcready_to_ps4_with_store(data, has_leftovers_flag, store, LeftoversPolicy::default())
+}
+
+/// `pcready_to_ps4_with_store` with `policy` deciding what happens when the leftovers are not in `store`
+pub fn pcready_to_ps4_with_store_and_policy(data: &[u8], has_leftovers_flag: bool, store: &mut dyn LeftoversStore, policy: LeftoversPolicy) -> Result<ConversionOutput, ConversionError> {
+    conversion::pcready_to_ps4_with_store(data, has_leftovers_flag, store, policy)
+}
+
+/// Automatically detects the format and converts accordingly, keeping leftovers in / looking them up in `store`
+pub fn convert_auto_with_store(data: &[u8], store: &mut dyn LeftoversStore) -> Result<ConversionOutput, ConversionError> {
+    conversion::convert_auto_with_store(data, store, LeftoversPolicy::default())
+}
+
+/// `convert_auto_with_store` with `policy` deciding what happens when the leftovers are not in `store`
+pub fn convert_auto_with_store_and_policy(data: &[u8], store: &mut dyn LeftoversStore, policy: LeftoversPolicy) -> Result<ConversionOutput, ConversionError> {
+    conversion::convert_auto_with_store(data, store, policy)
 }
 
 #[cfg(feature = "python")]
@@ -39,19 +116,66 @@ impl PyXenoverse2Converter {
         PyXenoverse2Converter {}
     }
 
-    fn ps4_to_pcready(&self, data: &[u8], input_path: &str, dir: &str) -> PyResult<Vec<u8>> {
-        ps4_to_pcready(data, input_path, dir)
-            .map_err(|e| pyo3::exceptions::PyException::new_err(e.to_string()))
+    fn ps4_to_pcready(&self, data: &[u8]) -> PyResult<PyConversionOutput> {
+        ps4_to_pcready(data)
+            .map(PyConversionOutput::from)
+            .map_err(python_errors::to_py_err)
+    }
+
+    /// `policy` is "zero_fill" (default) or "strict", see `LeftoversPolicy`
+    #[pyo3(signature = (data, has_leftovers_flag, leftovers=None, policy="zero_fill"))]
+    fn pcready_to_ps4(&self, data: &[u8], has_leftovers_flag: bool, leftovers: Option<&[u8]>, policy: &str) -> PyResult<PyConversionOutput> {
+        pcready_to_ps4_with_policy(data, has_leftovers_flag, leftovers, py_policy(policy)?)
+            .map(PyConversionOutput::from)
+            .map_err(python_errors::to_py_err)
+    }
+
+    #[pyo3(signature = (data, leftovers=None, policy="zero_fill"))]
+    fn convert_auto(&self, data: &[u8], leftovers: Option<&[u8]>, policy: &str) -> PyResult<PyConversionOutput> {
+        convert_auto_with_policy(data, leftovers, py_policy(policy)?)
+            .map(PyConversionOutput::from)
+            .map_err(python_errors::to_py_err)
     }
 
-    fn pcready_to_ps4(&self, data: &[u8], input_path: &str, dir: &str, has_leftovers_flag: bool) -> PyResult<Vec<u8>> {
-        pcready_to_ps4(data, input_path, dir, has_leftovers_flag)
-            .map_err(|e| pyo3::exceptions::PyException::new_err(e.to_string()))
+    /// Describes a save without converting it (see `SaveInfo`)
+    fn inspect<'py>(&self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+        let info = inspect(data).map_err(python_errors::to_py_err)?;
+        let dict = pyo3::types::PyDict::new(py);
+        dict.set_item("format", info.format.name())?;
+        dict.set_item("layout", info.layout.name)?;
+        dict.set_item("size", info.size)?;
+        dict.set_item("marker_version", info.marker_version)?;
+        dict.set_item("leftovers_flag", info.leftovers_flag)?;
+        dict.set_item("z_byte", info.z_byte)?;
+        dict.set_item("sav_offsets", info.sav_offsets)?;
+        dict.set_item("fill_len", info.fill_len)?;
+        dict.set_item("excess", info.excess)?;
+        dict.set_item("pad", info.pad)?;
+        dict.set_item("leftovers", info.leftovers)?;
+        dict.set_item("embedded", info.embedded)?;
+        dict.set_item("sha1", info.sha1)?;
+        dict.set_item("converted_sha1", info.converted_sha1)?;
+        dict.set_item("conversion_error", info.conversion_error.map(|e| e.to_string()))?;
+        dict.set_item("provenance", info.provenance.map(|check| check.status.name()))?;
+        Ok(dict)
     }
 
-    fn convert_auto(&self, data: &[u8], input_path: &str, dir: &str) -> PyResult<Vec<u8>> {
-        convert_auto(data, input_path, dir)
-            .map_err(|e| pyo3::exceptions::PyException::new_err(e.to_string()))
+    /// Whether a PC-ready save changed since it was packed, and what its provenance record says (see `ProvenanceCheck`)
+    fn check_provenance<'py>(&self, py: Python<'py>, data: &[u8]) -> PyResult<Bound<'py, pyo3::types::PyDict>> {
+        let check = check_provenance(data).map_err(python_errors::to_py_err)?;
+        let dict = pyo3::types::PyDict::new(py);
+        dict.set_item("status", check.status.name())?;
+        dict.set_item("tool_version", check.record.as_ref().map(|r| r.tool_version.clone()))?;
+        dict.set_item("ps4_sha1", check.record.as_ref().map(|r| r.ps4_sha1.clone()))?;
+        dict.set_item("body_sha1", check.record.as_ref().map(|r| r.body_sha1.clone()))?;
+        Ok(dict)
+    }
+
+    /// True if the MD5 header of a PS4 save matches the body it is assumed to cover (unverified, see md5_header)
+    fn check_md5_header(&self, data: &[u8]) -> PyResult<bool> {
+        check_md5_header(data)
+            .map(|status| status.matches)
+            .map_err(python_errors::to_py_err)
     }
 }
 
@@ -59,175 +183,480 @@ impl PyXenoverse2Converter {
 #[pyclass]
 pub struct PyXenoverse2Converter {}
 
+#[cfg(feature = "python")]
+fn py_policy(name: &str) -> PyResult<LeftoversPolicy> {
+    LeftoversPolicy::by_name(name).ok_or_else(|| {
+        pyo3::exceptions::PyValueError::new_err(format!("unknown leftovers policy {:?} (expected \"strict\" or \"zero_fill\")", name))
+    })
+}
+
+#[cfg(feature = "python")]
+/// Python view of `ConversionOutput`
+#[pyclass(name = "ConversionOutput", get_all)]
+pub struct PyConversionOutput {
+    data: Vec<u8>,
+    leftovers: Option<Vec<u8>>,
+    warnings: Vec<String>,
+    embedded: bool,
+}
+
+#[cfg(feature = "python")]
+impl From<ConversionOutput> for PyConversionOutput {
+    fn from(out: ConversionOutput) -> Self {
+        PyConversionOutput { data: out.data, leftovers: out.leftovers, warnings: out.warnings, embedded: out.embedded }
+    }
+}
+
+#[cfg(feature = "python")]
+/// One Python exception class per `ConversionError` variant, all deriving from `ConversionError`
+mod python_errors {
+    use pyo3::create_exception;
+    use pyo3::exceptions::PyException;
+    use pyo3::prelude::*;
+
+    create_exception!(xv2_converter_lib, ConversionError, PyException);
+    create_exception!(xv2_converter_lib, WrongSizeError, ConversionError);
+    create_exception!(xv2_converter_lib, MissingMagicError, ConversionError);
+    create_exception!(xv2_converter_lib, NoMatchingLayoutError, ConversionError);
+    create_exception!(xv2_converter_lib, UnrecognizedMarkerError, ConversionError);
+    create_exception!(xv2_converter_lib, UnsupportedVersionError, ConversionError);
+    create_exception!(xv2_converter_lib, MarkerLayoutMismatchError, ConversionError);
+    create_exception!(xv2_converter_lib, LeftoversMissingError, ConversionError);
+    create_exception!(xv2_converter_lib, LeftoversShortError, ConversionError);
+    create_exception!(xv2_converter_lib, LeftoversStorageError, ConversionError);
+    create_exception!(xv2_converter_lib, LeftoversMismatchError, ConversionError);
+    create_exception!(xv2_converter_lib, EmbeddedLeftoversCorruptError, ConversionError);
+    create_exception!(xv2_converter_lib, InvalidLayoutError, ConversionError);
+    create_exception!(xv2_converter_lib, SizeMismatchError, ConversionError);
+
+    pub fn to_py_err(e: super::ConversionError) -> PyErr {
+        use super::ConversionError as E;
+        let msg = e.to_string();
+        match e {
+            E::WrongSize { .. } => WrongSizeError::new_err(msg),
+            E::MissingMagic { .. } => MissingMagicError::new_err(msg),
+            E::NoMatchingLayout { .. } => NoMatchingLayoutError::new_err(msg),
+            E::UnrecognizedMarker => UnrecognizedMarkerError::new_err(msg),
+            E::UnsupportedVersion(_) => UnsupportedVersionError::new_err(msg),
+            E::MarkerLayoutMismatch => MarkerLayoutMismatchError::new_err(msg),
+            E::LeftoversMissing => LeftoversMissingError::new_err(msg),
+            E::LeftoversShort { .. } => LeftoversShortError::new_err(msg),
+            E::LeftoversStorage(_) => LeftoversStorageError::new_err(msg),
+            E::LeftoversMismatch(_) => LeftoversMismatchError::new_err(msg),
+            E::EmbeddedLeftoversCorrupt(_) => EmbeddedLeftoversCorruptError::new_err(msg),
+            E::InvalidLayout(_) => InvalidLayoutError::new_err(msg),
+            E::SizeMismatch { .. } => SizeMismatchError::new_err(msg),
+        }
+    }
+
+    pub fn register(m: &Bound<'_, PyModule>) -> PyResult<()> {
+        let py = m.py();
+        m.add("ConversionError", py.get_type::<ConversionError>())?;
+        m.add("WrongSizeError", py.get_type::<WrongSizeError>())?;
+        m.add("MissingMagicError", py.get_type::<MissingMagicError>())?;
+        m.add("NoMatchingLayoutError", py.get_type::<NoMatchingLayoutError>())?;
+        m.add("UnrecognizedMarkerError", py.get_type::<UnrecognizedMarkerError>())?;
+        m.add("UnsupportedVersionError", py.get_type::<UnsupportedVersionError>())?;
+        m.add("MarkerLayoutMismatchError", py.get_type::<MarkerLayoutMismatchError>())?;
+        m.add("LeftoversMissingError", py.get_type::<LeftoversMissingError>())?;
+        m.add("LeftoversShortError", py.get_type::<LeftoversShortError>())?;
+        m.add("LeftoversStorageError", py.get_type::<LeftoversStorageError>())?;
+        m.add("LeftoversMismatchError", py.get_type::<LeftoversMismatchError>())?;
+        m.add("EmbeddedLeftoversCorruptError", py.get_type::<EmbeddedLeftoversCorruptError>())?;
+        m.add("InvalidLayoutError", py.get_type::<InvalidLayoutError>())?;
+        m.add("SizeMismatchError", py.get_type::<SizeMismatchError>())?;
+        Ok(())
+    }
+}
+
 #[cfg(feature = "python")]
 #[pymodule]
 fn xv2_converter_lib(m: &Bound<'_, PyModule>) -> PyResult<()> {
     m.add_class::<PyXenoverse2Converter>()?;
+    m.add_class::<PyConversionOutput>()?;
+    python_errors::register(m)?;
     Ok(())
 }
 
 #[cfg(feature = "c")]
 mod c_api {
+    use std::cell::RefCell;
+    use std::ffi::CString;
     use std::os::raw::c_char;
-    use std::ffi::CStr;
     use std::ptr;
     use libc::{malloc, free, c_void};
-    
+
+    use super::{ConversionError, LeftoversPolicy};
+
+    // Error codes reported by xv2_last_error (keep in sync with xenoverse2_converter.h)
+    pub const XV2_OK: i32 = 0;
+    pub const XV2_ERR_INVALID_ARGUMENT: i32 = 1;
+    pub const XV2_ERR_OUT_OF_MEMORY: i32 = 2;
+    pub const XV2_ERR_WRONG_SIZE: i32 = 3;
+    pub const XV2_ERR_MISSING_MAGIC: i32 = 4;
+    pub const XV2_ERR_UNRECOGNIZED_MARKER: i32 = 5;
+    pub const XV2_ERR_UNSUPPORTED_VERSION: i32 = 6;
+    pub const XV2_ERR_MARKER_LAYOUT_MISMATCH: i32 = 7;
+    pub const XV2_ERR_LEFTOVERS_MISSING: i32 = 8;
+    pub const XV2_ERR_LEFTOVERS_SHORT: i32 = 9;
+    pub const XV2_ERR_INVALID_LAYOUT: i32 = 10;
+    pub const XV2_ERR_SIZE_MISMATCH: i32 = 11;
+    pub const XV2_ERR_LEFTOVERS_STORAGE: i32 = 12;
+    pub const XV2_ERR_NO_MATCHING_LAYOUT: i32 = 13;
+    pub const XV2_ERR_EMBEDDED_LEFTOVERS_CORRUPT: i32 = 14;
+    pub const XV2_ERR_LEFTOVERS_MISMATCH: i32 = 15;
+
+    // Leftovers policies (keep in sync with xenoverse2_converter.h)
+    pub const XV2_LEFTOVERS_ZERO_FILL: i32 = 0;
+    pub const XV2_LEFTOVERS_STRICT: i32 = 1;
+
+    thread_local! {
+        static LAST_ERROR: RefCell<(i32, CString)> = RefCell::new((XV2_OK, CString::default()));
+    }
+
+    fn set_last_error(code: i32, message: &str) {
+        let message = CString::new(message.replace('\0', "")).unwrap_or_default();
+        LAST_ERROR.with(|last| *last.borrow_mut() = (code, message));
+    }
+
+    /// Records the error for xv2_last_error and returns the NULL result
+    fn fail(code: i32, message: &str) -> *mut u8 {
+        set_last_error(code, message);
+        ptr::null_mut()
+    }
+
+    fn conversion_failed(e: ConversionError) -> *mut u8 {
+        let code = match e {
+            ConversionError::WrongSize { .. } => XV2_ERR_WRONG_SIZE,
+            ConversionError::MissingMagic { .. } => XV2_ERR_MISSING_MAGIC,
+            ConversionError::NoMatchingLayout { .. } => XV2_ERR_NO_MATCHING_LAYOUT,
+            ConversionError::UnrecognizedMarker => XV2_ERR_UNRECOGNIZED_MARKER,
+            ConversionError::UnsupportedVersion(_) => XV2_ERR_UNSUPPORTED_VERSION,
+            ConversionError::MarkerLayoutMismatch => XV2_ERR_MARKER_LAYOUT_MISMATCH,
+            ConversionError::LeftoversMissing => XV2_ERR_LEFTOVERS_MISSING,
+            ConversionError::LeftoversShort { .. } => XV2_ERR_LEFTOVERS_SHORT,
+            ConversionError::InvalidLayout(_) => XV2_ERR_INVALID_LAYOUT,
+            ConversionError::SizeMismatch { .. } => XV2_ERR_SIZE_MISMATCH,
+            ConversionError::LeftoversStorage(_) => XV2_ERR_LEFTOVERS_STORAGE,
+            ConversionError::LeftoversMismatch(_) => XV2_ERR_LEFTOVERS_MISMATCH,
+            ConversionError::EmbeddedLeftoversCorrupt(_) => XV2_ERR_EMBEDDED_LEFTOVERS_CORRUPT,
+        };
+        fail(code, &e.to_string())
+    }
+
+    /// Error code of the last failed call on this thread (XV2_OK after a successful one)
+    #[no_mangle]
+    pub extern "C" fn xv2_last_error() -> i32 {
+        LAST_ERROR.with(|last| last.borrow().0)
+    }
+
+    /// Message of the last failed call on this thread; valid until the next call on the same thread
+    #[no_mangle]
+    pub extern "C" fn xv2_last_error_message() -> *const c_char {
+        LAST_ERROR.with(|last| last.borrow().1.as_ptr())
+    }
+
+    /// Copies `bytes` into a malloc'd buffer the caller releases with free_buffer
+    fn to_c_buffer(bytes: &[u8]) -> *mut u8 {
+        let output_ptr = unsafe { malloc(bytes.len().max(1)) as *mut u8 };
+        if !output_ptr.is_null() {
+            unsafe {
+                std::ptr::copy_nonoverlapping(bytes.as_ptr(), output_ptr, bytes.len());
+            }
+        }
+        output_ptr
+    }
+
+    /// Borrows optional leftovers passed from C (null pointer means "none")
+    unsafe fn leftovers_arg<'a>(leftovers: *const u8, leftovers_len: usize) -> Option<&'a [u8]> {
+        if leftovers.is_null() {
+            None
+        } else {
+            Some(std::slice::from_raw_parts(leftovers, leftovers_len))
+        }
+    }
+
     /// C-compatible function to convert PS4 to PC-ready format
     /// Returns a pointer to the converted data (caller must free with free_buffer)
+    /// If leftovers were produced, `*out_leftovers` receives a buffer (also freed with free_buffer), otherwise NULL
     #[no_mangle]
     pub extern "C" fn ps4_to_pcready_c(
         data: *const u8,
         data_len: usize,
-        input_path: *const c_char,
-        dir: *const c_char,
         out_len: *mut usize,
+        out_leftovers: *mut *mut u8,
+        out_leftovers_len: *mut usize,
     ) -> *mut u8 {
-        if data.is_null() || input_path.is_null() || dir.is_null() || out_len.is_null() {
-            return ptr::null_mut();
+        if data.is_null() || out_len.is_null() || out_leftovers.is_null() || out_leftovers_len.is_null() {
+            return fail(XV2_ERR_INVALID_ARGUMENT, "NULL argument");
         }
 
         let input_data = unsafe { std::slice::from_raw_parts(data, data_len) };
-        
-        let input_path_str = unsafe {
-            match CStr::from_ptr(input_path).to_str() {
-                Ok(s) => s,
-                Err(_) => return ptr::null_mut(),
-            }
-        };
-        
-        let dir_str = unsafe {
-            match CStr::from_ptr(dir).to_str() {
-                Ok(s) => s,
-                Err(_) => return ptr::null_mut(),
-            }
-        };
 
-        match super::ps4_to_pcready(input_data, input_path_str, dir_str) {
-            Ok(result) => {
-                unsafe { *out_len = result.len(); }
-                
-                // Allocate memory and copy the result
-                let output_ptr = unsafe { 
-                    malloc(result.len()) as *mut u8
-                };
-                
-                if output_ptr.is_null() {
-                    return ptr::null_mut();
-                }
-                
-                unsafe {
-                    std::ptr::copy_nonoverlapping(result.as_ptr(), output_ptr, result.len());
+        match super::ps4_to_pcready(input_data) {
+            Ok(result) => unsafe {
+                output_with_leftovers(&result.data, result.leftovers.as_deref(), out_len, out_leftovers, out_leftovers_len)
+            },
+            Err(e) => conversion_failed(e),
+        }
+    }
+
+    /// Hands `data` and the leftovers file (if any) to the caller as malloc'd buffers
+    /// Safety: the out pointers must be valid for writes
+    unsafe fn output_with_leftovers(
+        data: &[u8],
+        leftovers: Option<&[u8]>,
+        out_len: *mut usize,
+        out_leftovers: *mut *mut u8,
+        out_leftovers_len: *mut usize,
+    ) -> *mut u8 {
+        let output_ptr = to_c_buffer(data);
+        if output_ptr.is_null() {
+            return fail(XV2_ERR_OUT_OF_MEMORY, "out of memory");
+        }
+
+        let (leftovers_ptr, leftovers_len) = match leftovers {
+            Some(lf) => {
+                let lf_ptr = to_c_buffer(lf);
+                if lf_ptr.is_null() {
+                    free(output_ptr as *mut c_void);
+                    return fail(XV2_ERR_OUT_OF_MEMORY, "out of memory");
                 }
-                
-                output_ptr
+                (lf_ptr, lf.len())
             }
-            Err(_) => ptr::null_mut(),
+            None => (ptr::null_mut(), 0),
+        };
+
+        *out_len = data.len();
+        *out_leftovers = leftovers_ptr;
+        *out_leftovers_len = leftovers_len;
+
+        set_last_error(XV2_OK, "");
+        output_ptr
+    }
+
+    /// Policy from its XV2_LEFTOVERS_* value
+    fn policy_arg(policy: i32) -> Option<LeftoversPolicy> {
+        match policy {
+            XV2_LEFTOVERS_ZERO_FILL => Some(LeftoversPolicy::ZeroFill),
+            XV2_LEFTOVERS_STRICT => Some(LeftoversPolicy::Strict),
+            _ => None,
         }
     }
 
     /// C-compatible function to convert PC-ready to PS4 format
+    /// `leftovers` may be NULL when no leftovers are available
     #[no_mangle]
     pub extern "C" fn pcready_to_ps4_c(
         data: *const u8,
         data_len: usize,
-        input_path: *const c_char,
-        dir: *const c_char,
         has_leftovers_flag: bool,
+        leftovers: *const u8,
+        leftovers_len: usize,
         out_len: *mut usize,
     ) -> *mut u8 {
-        if data.is_null() || input_path.is_null() || dir.is_null() || out_len.is_null() {
-            return ptr::null_mut();
+        pcready_to_ps4_policy_c(data, data_len, has_leftovers_flag, leftovers, leftovers_len, XV2_LEFTOVERS_ZERO_FILL, out_len)
+    }
+
+    /// `pcready_to_ps4_c` with `policy` (XV2_LEFTOVERS_*) deciding what happens when leftovers are missing or too short
+    #[no_mangle]
+    pub extern "C" fn pcready_to_ps4_policy_c(
+        data: *const u8,
+        data_len: usize,
+        has_leftovers_flag: bool,
+        leftovers: *const u8,
+        leftovers_len: usize,
+        policy: i32,
+        out_len: *mut usize,
+    ) -> *mut u8 {
+        if data.is_null() || out_len.is_null() {
+            return fail(XV2_ERR_INVALID_ARGUMENT, "NULL argument");
         }
+        let Some(policy) = policy_arg(policy) else {
+            return fail(XV2_ERR_INVALID_ARGUMENT, "unknown leftovers policy");
+        };
 
         let input_data = unsafe { std::slice::from_raw_parts(data, data_len) };
-        
-        let input_path_str = unsafe {
-            match CStr::from_ptr(input_path).to_str() {
-                Ok(s) => s,
-                Err(_) => return ptr::null_mut(),
-            }
-        };
-        
-        let dir_str = unsafe {
-            match CStr::from_ptr(dir).to_str() {
-                Ok(s) => s,
-                Err(_) => return ptr::null_mut(),
-            }
-        };
+        let leftovers = unsafe { leftovers_arg(leftovers, leftovers_len) };
 
-        match super::pcready_to_ps4(input_data, input_path_str, dir_str, has_leftovers_flag) {
+        match super::pcready_to_ps4_with_policy(input_data, has_leftovers_flag, leftovers, policy) {
             Ok(result) => {
-                unsafe { *out_len = result.len(); }
-                
-                // Allocate memory and copy the result
-                let output_ptr = unsafe { 
-                    malloc(result.len()) as *mut u8
-                };
-                
+                let output_ptr = to_c_buffer(&result.data);
                 if output_ptr.is_null() {
-                    return ptr::null_mut();
-                }
-                
-                unsafe {
-                    std::ptr::copy_nonoverlapping(result.as_ptr(), output_ptr, result.len());
+                    return fail(XV2_ERR_OUT_OF_MEMORY, "out of memory");
                 }
-                
+                unsafe { *out_len = result.data.len(); }
+                set_last_error(XV2_OK, "");
                 output_ptr
             }
-            Err(_) => ptr::null_mut(),
+            Err(e) => conversion_failed(e),
         }
     }
 
     /// C-compatible function to automatically detect and convert save format
+    /// `leftovers` may be NULL when no leftovers are available
+    /// When a PS4 save is packed and produces leftovers, `*out_leftovers` receives them as for ps4_to_pcready_c,
+    /// otherwise NULL
     #[no_mangle]
     pub extern "C" fn convert_auto_c(
         data: *const u8,
         data_len: usize,
-        input_path: *const c_char,
-        dir: *const c_char,
+        leftovers: *const u8,
+        leftovers_len: usize,
+        out_len: *mut usize,
+        out_leftovers: *mut *mut u8,
+        out_leftovers_len: *mut usize,
+    ) -> *mut u8 {
+        convert_auto_policy_c(data, data_len, leftovers, leftovers_len, XV2_LEFTOVERS_ZERO_FILL, out_len, out_leftovers, out_leftovers_len)
+    }
+
+    /// `convert_auto_c` with `policy` (XV2_LEFTOVERS_*) deciding what happens when leftovers are missing or too short
+    #[no_mangle]
+    pub extern "C" fn convert_auto_policy_c(
+        data: *const u8,
+        data_len: usize,
+        leftovers: *const u8,
+        leftovers_len: usize,
+        policy: i32,
         out_len: *mut usize,
+        out_leftovers: *mut *mut u8,
+        out_leftovers_len: *mut usize,
     ) -> *mut u8 {
-        if data.is_null() || input_path.is_null() || dir.is_null() || out_len.is_null() {
-            return ptr::null_mut();
+        if data.is_null() || out_len.is_null() || out_leftovers.is_null() || out_leftovers_len.is_null() {
+            return fail(XV2_ERR_INVALID_ARGUMENT, "NULL argument");
         }
+        let Some(policy) = policy_arg(policy) else {
+            return fail(XV2_ERR_INVALID_ARGUMENT, "unknown leftovers policy");
+        };
 
         let input_data = unsafe { std::slice::from_raw_parts(data, data_len) };
-        
-        let input_path_str = unsafe {
-            match CStr::from_ptr(input_path).to_str() {
-                Ok(s) => s,
-                Err(_) => return ptr::null_mut(),
-            }
-        };
-        
-        let dir_str = unsafe {
-            match CStr::from_ptr(dir).to_str() {
-                Ok(s) => s,
-                Err(_) => return ptr::null_mut(),
-            }
-        };
+        let leftovers = unsafe { leftovers_arg(leftovers, leftovers_len) };
+        // Unpacking reports the restored HCD tail in `leftovers`; only packing has leftovers to hand back
+        let packing = super::detect(input_data).is_ok_and(|d| d.format == super::SaveFormat::Ps4);
 
-        match super::convert_auto(input_data, input_path_str, dir_str) {
-            Ok(result) => {
-                unsafe { *out_len = result.len(); }
-                
-                // Allocate memory and copy the result
-                let output_ptr = unsafe { 
-                    malloc(result.len()) as *mut u8
+        match super::convert_auto_with_policy(input_data, leftovers, policy) {
+            Ok(result) => unsafe {
+                let produced = result.leftovers.as_deref().filter(|_| packing);
+                output_with_leftovers(&result.data, produced, out_len, out_leftovers, out_leftovers_len)
+            },
+            Err(e) => conversion_failed(e),
+        }
+    }
+
+    /// C view of `SaveInfo` (keep in sync with xenoverse2_converter.h)
+    #[repr(C)]
+    pub struct Xv2SaveInfo {
+        /// 0 = PS4, 1 = PC-ready
+        pub format: i32,
+        pub layout_name: [c_char; 32],
+        pub size: usize,
+        /// -1 when there is no marker
+        pub marker_version: i32,
+        /// -1 when there is no marker
+        pub leftovers_flag: i32,
+        pub z_byte: u8,
+        /// First XV2_MAX_SAV_OFFSETS '#SAV' offsets; sav_offset_count is the total found
+        pub sav_offsets: [usize; 8],
+        pub sav_offset_count: usize,
+        pub fill_len: usize,
+        pub excess: usize,
+        pub pad: usize,
+        pub leftovers: bool,
+        pub sha1: [c_char; 41],
+        /// Empty string when the converted save depends on leftovers
+        pub converted_sha1: [c_char; 41],
+        pub embedded: bool,
+        /// XV2_PROVENANCE_*, -1 for PS4 saves
+        pub provenance: i32,
+        /// SHA1 of the PS4 save the provenance record names; empty without a record
+        pub packed_from_sha1: [c_char; 41],
+        /// Why the save does not convert (converted_sha1 is empty then); empty when it does
+        pub conversion_error: [c_char; 256],
+    }
+
+    /// Copies `s` into a fixed NUL-terminated C string field, truncating if needed
+    fn copy_c_str(dst: &mut [c_char], s: &str) {
+        let n = s.len().min(dst.len() - 1);
+        for (d, b) in dst.iter_mut().zip(&s.as_bytes()[..n]) {
+            *d = *b as c_char;
+        }
+        dst[n] = 0;
+    }
+
+    /// C-compatible function to describe a save without converting it
+    /// Returns true and fills `*out_info` on success, false on error (see xv2_last_error)
+    #[no_mangle]
+    pub extern "C" fn inspect_c(data: *const u8, data_len: usize, out_info: *mut Xv2SaveInfo) -> bool {
+        if data.is_null() || out_info.is_null() {
+            fail(XV2_ERR_INVALID_ARGUMENT, "NULL argument");
+            return false;
+        }
+
+        let input_data = unsafe { std::slice::from_raw_parts(data, data_len) };
+
+        match super::inspect(input_data) {
+            Ok(info) => {
+                let out = unsafe { &mut *out_info };
+                out.format = match info.format {
+                    super::SaveFormat::Ps4 => 0,
+                    super::SaveFormat::PcReady => 1,
                 };
-                
-                if output_ptr.is_null() {
-                    return ptr::null_mut();
-                }
-                
-                unsafe {
-                    std::ptr::copy_nonoverlapping(result.as_ptr(), output_ptr, result.len());
+                copy_c_str(&mut out.layout_name, info.layout.name);
+                out.size = info.size;
+                out.marker_version = info.marker_version.map_or(-1, i32::from);
+                out.leftovers_flag = info.leftovers_flag.map_or(-1, i32::from);
+                out.z_byte = info.z_byte;
+                out.sav_offsets = [0; 8];
+                for (dst, src) in out.sav_offsets.iter_mut().zip(&info.sav_offsets) {
+                    *dst = *src;
                 }
-                
-                output_ptr
+                out.sav_offset_count = info.sav_offsets.len();
+                out.fill_len = info.fill_len;
+                out.excess = info.excess;
+                out.pad = info.pad;
+                out.leftovers = info.leftovers;
+                copy_c_str(&mut out.sha1, &info.sha1);
+                copy_c_str(&mut out.converted_sha1, info.converted_sha1.as_deref().unwrap_or(""));
+                out.embedded = info.embedded;
+                out.provenance = info.provenance.as_ref().map_or(-1, |check| match check.status {
+                    super::ProvenanceStatus::Missing => 0,
+                    super::ProvenanceStatus::Unmodified => 1,
+                    super::ProvenanceStatus::Modified => 2,
+                });
+                let packed_from = info.provenance.as_ref().and_then(|check| check.record.as_ref()).map(|r| r.ps4_sha1.as_str());
+                copy_c_str(&mut out.packed_from_sha1, packed_from.unwrap_or(""));
+                let conversion_error = info.conversion_error.map(|e| e.to_string());
+                copy_c_str(&mut out.conversion_error, conversion_error.as_deref().unwrap_or(""));
+                set_last_error(XV2_OK, "");
+                true
+            }
+            Err(e) => {
+                conversion_failed(e);
+                false
+            }
+        }
+    }
+
+    /// C-compatible function to check the MD5 header of a PS4 save
+    /// Returns 1 if it matches, 0 if it does not, -1 on error (see xv2_last_error)
+    #[no_mangle]
+    pub extern "C" fn check_md5_header_c(data: *const u8, data_len: usize) -> i32 {
+        if data.is_null() {
+            fail(XV2_ERR_INVALID_ARGUMENT, "NULL argument");
+            return -1;
+        }
+
+        let input_data = unsafe { std::slice::from_raw_parts(data, data_len) };
+
+        match super::check_md5_header(input_data) {
+            Ok(status) => {
+                set_last_error(XV2_OK, "");
+                status.matches as i32
+            }
+            Err(e) => {
+                conversion_failed(e);
+                -1
             }
-            Err(_) => ptr::null_mut(),
         }
     }
 
@@ -240,4 +669,4 @@ mod c_api {
             }
         }
     }
-}
\ No newline at end of file
+}
@@ -1,4 +1,5 @@
 use crate::constants::*;
+use crate::layout::SaveLayout;
 
 pub fn has_dual_magic(data: &[u8]) -> bool {
     data.len() >= 0xA4  // Need at least 0xA4 bytes to check both positions
@@ -37,11 +38,14 @@ pub fn has_any_marker_at_08(d: &[u8]) -> bool {
 }
 
 pub fn try_read_marker(d: &[u8]) -> Option<(u8, u8)> {
-    if d.len() < MARKER_OFFSET + 8 {
+    try_read_marker_at(d, MARKER_OFFSET)
+}
+
+pub fn try_read_marker_at(d: &[u8], o: usize) -> Option<(u8, u8)> {
+    if d.len() < o + MARKER_LEN {
         return None;
     }
 
-    let o = MARKER_OFFSET;
     if d[o] != MARK0 { return None; }
     if d[o + 1] != MARK1 { return None; }
     if d[o + 2] != MARK2 { return None; }
@@ -53,7 +57,7 @@ pub fn try_read_marker(d: &[u8]) -> Option<(u8, u8)> {
     let version = d[o + 7];
 
     let flag_ok = leftovers_flag == FLAG_NO_LEFTOVERS || leftovers_flag == FLAG_LEFTOVERS;
-    let ver_ok = version == VER_V2;
+    let ver_ok = version == VER_V2 || version == VER_V3;
 
     if flag_ok && ver_ok {
         Some((version, leftovers_flag))
@@ -64,12 +68,17 @@ pub fn try_read_marker(d: &[u8]) -> Option<(u8, u8)> {
 
 
 pub fn looks_like_v2(d: &[u8]) -> bool {
-    if let Some((ver, _)) = try_read_marker(d) {
-        if ver != VER_V2 { return false; }
+    looks_like_v2_in(d, &SaveLayout::default())
+}
+
+/// v3 shares the v2 layout, so it passes too
+pub fn looks_like_v2_in(d: &[u8], layout: &SaveLayout) -> bool {
+    if let Some((ver, _)) = try_read_marker_at(d, layout.marker_offset) {
+        if ver != VER_V2 && ver != VER_V3 { return false; }
 
         // v2: [SAV_HEADER] section starts at EOF-PREFIX_SIZE-HEADER_SIZE, [Z_BYTE] is at EOF-PREFIX_SIZE-HEADER_SIZE-1
-        if d.len() != EDITOR_SIZE { return false; }
-        let a_start = d.len() - MD5_HEADER_SIZE - SAV_HEADER_SIZE;  // Position of A section in PC-ready format
+        if d.len() != layout.editor_size { return false; }
+        let a_start = layout.pcready_sav_header_offset();  // Position of A section in PC-ready format
         if !has_magic_at(d, a_start) { return false; }
         // Z byte must be preserved as-is, so we accept any value
         return true;
@@ -0,0 +1,142 @@
+use md5::{Digest, Md5};
+
+use crate::detect;
+use crate::error::{ConversionError, Result};
+use crate::marker;
+
+// What the header holds is an assumption, not taken from any specification or from the game: no documentation
+// of the PS4 save format was available. It is inferred from the header being 0x20 bytes (exactly 32 hex digits,
+// or room for a 16-byte digest) and is taken to cover everything after the header, up to the end of the file.
+// check_md5_header picks the encoding from the header's bytes; callers who know it use check_md5_header_as.
+// The check is read-only and unverified: nothing here rewrites a header, since a wrong guess would break a
+// save the console accepts.
+
+/// How the digest is stored in the MD5 header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Md5Encoding {
+    /// 32 ASCII hex digits filling the whole header
+    Hex,
+    /// 16 raw digest bytes at the start of the header
+    Raw,
+}
+
+/// Result of checking the MD5 header of a PS4 save against the body it covers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Md5Status {
+    /// Digest as stored in the header
+    pub stored: [u8; 16],
+    /// Digest of everything after the header
+    pub computed: [u8; 16],
+    pub encoding: Md5Encoding,
+    pub matches: bool,
+}
+
+impl Md5Encoding {
+    /// Guesses the encoding of `header`: hex if it is 32 hex digits, raw otherwise
+    pub fn guess(header: &[u8]) -> Self {
+        if header.len() == 0x20 && header.iter().all(u8::is_ascii_hexdigit) {
+            Md5Encoding::Hex
+        } else {
+            Md5Encoding::Raw
+        }
+    }
+}
+
+/// Checks the MD5 header of a PS4 save, guessing its encoding (see Md5Encoding::guess)
+pub fn check_md5_header(ps4: &[u8]) -> Result<Md5Status> {
+    let header_size = ps4_header_size(ps4)?;
+    check_md5_header_as(ps4, Md5Encoding::guess(&ps4[0..header_size]))
+}
+
+/// Checks the MD5 header of a PS4 save, reading the stored digest with `encoding`
+pub fn check_md5_header_as(ps4: &[u8], encoding: Md5Encoding) -> Result<Md5Status> {
+    let header_size = ps4_header_size(ps4)?;
+    if encoding == Md5Encoding::Hex && header_size < 0x20 {
+        return Err(ConversionError::InvalidLayout("MD5 header too small for a hex digest"));
+    }
+    let header = &ps4[0..header_size];
+    let computed: [u8; 16] = Md5::digest(&ps4[header_size..]).into();
+
+    let stored = match encoding {
+        Md5Encoding::Hex => decode_hex(header),
+        Md5Encoding::Raw => header[0..16].try_into().expect("header holds at least 16 bytes"),
+    };
+
+    Ok(Md5Status { stored, computed, encoding, matches: stored == computed })
+}
+
+fn ps4_header_size(ps4: &[u8]) -> Result<usize> {
+    let layout = detect::layout_for_ps4(ps4);
+    if ps4.len() != layout.ps4_size {
+        return Err(ConversionError::WrongSize { expected: layout.ps4_size, actual: ps4.len() });
+    }
+    for offset in layout.ps4_magic_offsets() {
+        if !marker::has_magic_at(ps4, offset) {
+            return Err(ConversionError::MissingMagic { offset });
+        }
+    }
+    if layout.md5_header_size < 16 {
+        return Err(ConversionError::InvalidLayout("MD5 header smaller than a digest"));
+    }
+    Ok(layout.md5_header_size)
+}
+
+fn decode_hex(hex: &[u8]) -> [u8; 16] {
+    let mut out = [0u8; 16];
+    for (i, pair) in hex.chunks(2).take(16).enumerate() {
+        let digits = std::str::from_utf8(pair).unwrap_or("00");
+        out[i] = u8::from_str_radix(digits, 16).unwrap_or(0);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::{synthetic_ps4, TAIL_LEN};
+
+    fn digest(ps4: &[u8]) -> [u8; 16] {
+        Md5::digest(&ps4[0x20..]).into()
+    }
+
+    #[test]
+    fn hex_header_is_read_as_hex() {
+        let mut ps4 = synthetic_ps4(&[7; TAIL_LEN]);
+        let hex = crate::utils::to_hex(&digest(&ps4));
+        ps4[..0x20].copy_from_slice(hex.as_bytes());
+        let status = check_md5_header(&ps4).unwrap();
+        assert_eq!(status.encoding, Md5Encoding::Hex);
+        assert!(status.matches);
+    }
+
+    #[test]
+    fn raw_header_is_read_as_raw() {
+        let mut ps4 = synthetic_ps4(&[7; TAIL_LEN]);
+        let raw = digest(&ps4);
+        ps4[..16].copy_from_slice(&raw);
+        let status = check_md5_header(&ps4).unwrap();
+        assert_eq!(status.encoding, Md5Encoding::Raw);
+        assert!(status.matches);
+        assert!(!check_md5_header_as(&ps4, Md5Encoding::Hex).unwrap().matches);
+    }
+
+    #[test]
+    fn stale_header_does_not_match() {
+        let mut ps4 = synthetic_ps4(&[7; TAIL_LEN]);
+        let raw = digest(&ps4);
+        ps4[..16].copy_from_slice(&raw);
+        ps4[0x1000] ^= 0xFF;
+        let status = check_md5_header(&ps4).unwrap();
+        assert!(!status.matches);
+        assert_eq!(status.stored, raw);
+    }
+
+    #[test]
+    fn check_does_not_take_other_saves() {
+        let ps4 = synthetic_ps4(&[7; TAIL_LEN]);
+        assert!(matches!(check_md5_header(&ps4[1..]), Err(ConversionError::WrongSize { .. })));
+        let mut no_magic = ps4.clone();
+        no_magic[0xA0] = 0;
+        assert_eq!(check_md5_header(&no_magic), Err(ConversionError::MissingMagic { offset: 0xA0 }));
+    }
+}
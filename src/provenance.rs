@@ -0,0 +1,179 @@
+//! Provenance record kept at the end of the fill gap of a PC-ready save, telling whether it changed since it was packed.
+//!
+//! Record layout: ["XV2P"][record version u8][converter version: major u8, minor u8, patch u8]
+//! [SHA1 of the original PS4 save, 20 bytes][SHA1 of the PC-ready save with this record zeroed, 20 bytes]
+
+use std::ops::Range;
+
+use crate::conversion::pack_geometry;
+use crate::detect;
+use crate::error::{ConversionError, Result};
+use crate::layout::SaveLayout;
+use crate::utils;
+
+const MAGIC: &[u8; 4] = b"XV2P";
+const RECORD_VERSION: u8 = 1;
+pub(crate) const RECORD_LEN: usize = 4 + 1 + 3 + 20 + 20;
+
+/// What the provenance record of a PC-ready save says about where it came from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Provenance {
+    /// Version of the converter that packed the save
+    pub tool_version: String,
+    /// SHA1 of the PS4 save it was packed from
+    pub ps4_sha1: String,
+    /// SHA1 of the packed save, with the record zeroed
+    pub body_sha1: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProvenanceStatus {
+    /// Byte for byte what the converter packed
+    Unmodified,
+    /// Changed since it was packed, typically saved again by an editor
+    Modified,
+    /// No provenance record: packed by an older build, or the editor cleared the fill gap
+    Missing,
+}
+
+impl ProvenanceStatus {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ProvenanceStatus::Unmodified => "unmodified",
+            ProvenanceStatus::Modified => "modified by editor",
+            ProvenanceStatus::Missing => "provenance missing",
+        }
+    }
+}
+
+/// Outcome of `check_provenance`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProvenanceCheck {
+    pub status: ProvenanceStatus,
+    /// The record, unless it is missing
+    pub record: Option<Provenance>,
+}
+
+/// Where the record sits in a PC-ready save of `layout`; `None` if its fill gap is too small to hold one
+pub(crate) fn record_range(layout: &SaveLayout) -> Option<Range<usize>> {
+    let fill_len = pack_geometry(layout).ok()?.fill_len;
+    let end = layout.hcd_start_pc_ready;
+    (fill_len >= RECORD_LEN && end >= RECORD_LEN).then(|| end - RECORD_LEN..end)
+}
+
+/// Writes the record into `pcready`, just packed from the PS4 save with SHA1 `ps4_sha1`
+pub(crate) fn write(pcready: &mut [u8], layout: &SaveLayout, ps4_sha1: [u8; 20]) {
+    let Some(range) = record_range(layout) else { return };
+    pcready[range.clone()].fill(0);
+    let body_sha1 = utils::sha1(pcready);
+
+    let mut record = Vec::with_capacity(RECORD_LEN);
+    record.extend_from_slice(MAGIC);
+    record.push(RECORD_VERSION);
+    record.extend_from_slice(&tool_version());
+    record.extend_from_slice(&ps4_sha1);
+    record.extend_from_slice(&body_sha1);
+    pcready[range].copy_from_slice(&record);
+}
+
+/// Reads the provenance record of a PC-ready save and checks it against the rest of the save
+pub fn check_provenance(data: &[u8]) -> Result<ProvenanceCheck> {
+    let layout = detect::layout_for_pcready(data);
+    if data.len() != layout.editor_size {
+        return Err(ConversionError::WrongSize { expected: layout.editor_size, actual: data.len() });
+    }
+    let missing = ProvenanceCheck { status: ProvenanceStatus::Missing, record: None };
+    let Some(range) = record_range(layout) else { return Ok(missing) };
+    let bytes = &data[range.clone()];
+    if !bytes.starts_with(MAGIC) || bytes[4] != RECORD_VERSION {
+        return Ok(missing);
+    }
+
+    let record = Provenance {
+        tool_version: format!("{}.{}.{}", bytes[5], bytes[6], bytes[7]),
+        ps4_sha1: utils::to_hex(&bytes[8..28]),
+        body_sha1: utils::to_hex(&bytes[28..48]),
+    };
+    let mut body = data.to_vec();
+    body[range].fill(0);
+    let status = match utils::sha1_hex(&body) == record.body_sha1 {
+        true => ProvenanceStatus::Unmodified,
+        false => ProvenanceStatus::Modified,
+    };
+    Ok(ProvenanceCheck { status, record: Some(record) })
+}
+
+/// This build's version as major, minor, patch
+fn tool_version() -> [u8; 3] {
+    let mut parts = env!("CARGO_PKG_VERSION").split(['.', '-']).map(|p| p.parse().unwrap_or(0));
+    [(); 3].map(|_| parts.next().unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::DEFAULT_LAYOUT;
+
+    const PS4_SHA1: [u8; 20] = [0x5A; 20];
+
+    /// Synthetic PC-ready sized buffer with a record written into it
+    fn packed() -> Vec<u8> {
+        let mut data: Vec<u8> = (0..DEFAULT_LAYOUT.editor_size).map(|i| (i % 251) as u8).collect();
+        write(&mut data, &DEFAULT_LAYOUT, PS4_SHA1);
+        data
+    }
+
+    #[test]
+    fn record_sits_at_the_end_of_the_fill_gap() {
+        let range = record_range(&DEFAULT_LAYOUT).expect("the default fill gap holds a record");
+        assert_eq!(range.end, DEFAULT_LAYOUT.hcd_start_pc_ready);
+        assert_eq!(range.len(), RECORD_LEN);
+        assert!(packed()[range].starts_with(MAGIC));
+    }
+
+    #[test]
+    fn unmodified_save_matches_its_record() {
+        let check = check_provenance(&packed()).unwrap();
+        assert_eq!(check.status, ProvenanceStatus::Unmodified);
+        let record = check.record.unwrap();
+        assert_eq!(record.ps4_sha1, utils::to_hex(&PS4_SHA1));
+        assert_eq!(record.tool_version, env!("CARGO_PKG_VERSION").split('-').next().unwrap());
+    }
+
+    #[test]
+    fn edited_save_is_modified() {
+        let mut data = packed();
+        data[0x100] ^= 0xFF;
+        let check = check_provenance(&data).unwrap();
+        assert_eq!(check.status, ProvenanceStatus::Modified);
+        assert_eq!(check.record.unwrap().ps4_sha1, utils::to_hex(&PS4_SHA1));
+    }
+
+    #[test]
+    fn damaged_body_sha1_is_modified() {
+        let mut data = packed();
+        data[DEFAULT_LAYOUT.hcd_start_pc_ready - 1] ^= 0xFF;
+        assert_eq!(check_provenance(&data).unwrap().status, ProvenanceStatus::Modified);
+    }
+
+    #[test]
+    fn zeroed_or_unknown_record_is_missing() {
+        let range = record_range(&DEFAULT_LAYOUT).unwrap();
+        let missing = ProvenanceCheck { status: ProvenanceStatus::Missing, record: None };
+
+        let mut zeroed = packed();
+        zeroed[range.clone()].fill(0);
+        assert_eq!(check_provenance(&zeroed), Ok(missing.clone()));
+
+        let mut version = packed();
+        version[range.start + 4] = RECORD_VERSION + 1;
+        assert_eq!(check_provenance(&version), Ok(missing));
+    }
+
+    #[test]
+    fn truncated_save_is_refused() {
+        let data = packed();
+        assert_eq!(check_provenance(&data[..data.len() - 1]),
+                   Err(ConversionError::WrongSize { expected: DEFAULT_LAYOUT.editor_size, actual: data.len() - 1 }));
+    }
+}
@@ -0,0 +1,39 @@
+//! Synthetic saves for unit tests; no real save data is needed to exercise the converter.
+
+use crate::constants::MAGIC;
+use crate::layout::DEFAULT_LAYOUT;
+
+/// Bytes trimmed from the end of the HCD when packing a save of DEFAULT_LAYOUT
+pub const TAIL_LEN: usize = 0xA0;
+
+/// PS4 save of DEFAULT_LAYOUT filled with a repeating pattern, with '#SAV' where the layout needs it,
+/// `tail` as the last bytes of the HCD (the part packing trims) and Z byte 0x21
+pub fn synthetic_ps4(tail: &[u8; TAIL_LEN]) -> Vec<u8> {
+    let layout = DEFAULT_LAYOUT;
+    let mut data: Vec<u8> = (0..layout.ps4_size).map(|i| (i % 251) as u8 | 1).collect();
+    for offset in layout.ps4_magic_offsets() {
+        data[offset..offset + MAGIC.len()].copy_from_slice(&MAGIC);
+    }
+    let z_index = layout.ps4_size - 1;
+    data[z_index - TAIL_LEN..z_index].copy_from_slice(tail);
+    data[z_index] = 0x21;
+    data
+}
+
+/// HCD tail PackBits cannot shrink, too large for the fill gap
+pub fn random_tail() -> [u8; TAIL_LEN] {
+    let mut state = 0x2545_F491u32;
+    [(); TAIL_LEN].map(|_| {
+        state ^= state << 13;
+        state ^= state >> 17;
+        state ^= state << 5;
+        state as u8
+    })
+}
+
+/// HCD tail that fits in the fill gap once run-length encoded
+pub fn compressible_tail() -> [u8; TAIL_LEN] {
+    let mut tail = [0xEE; TAIL_LEN];
+    tail[TAIL_LEN - 0x10..].fill(0);
+    tail
+}
@@ -1,19 +1,38 @@
 use sha1::{Sha1, Digest};
 use std::fmt::Write;
 
-pub fn sha1_hex(bytes: &[u8]) -> String {
+pub fn sha1(bytes: &[u8]) -> [u8; 20] {
     let mut hasher = Sha1::new();
     hasher.update(bytes);
-    let hash = hasher.finalize();
-    
-    let mut result = String::with_capacity(40);
-    for byte in hash {
+    hasher.finalize().into()
+}
+
+pub fn sha1_hex(bytes: &[u8]) -> String {
+    to_hex(&sha1(bytes))
+}
+
+pub fn to_hex(bytes: &[u8]) -> String {
+    let mut result = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
         write!(result, "{:02x}", byte).unwrap();
     }
     result
 }
 
 
+/// CRC-32 (IEEE 802.3, as used by zip and PNG)
+pub fn crc32(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in bytes {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xEDB8_8320 } else { crc >> 1 };
+        }
+    }
+    !crc
+}
+
+
 pub fn all_zero(bytes: &[u8]) -> bool {
     bytes.iter().all(|&b| b == 0)
 }
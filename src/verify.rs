@@ -0,0 +1,295 @@
+use std::fmt;
+
+use crate::conversion::{self, LeftoversPolicy};
+use crate::detect::{self, SaveFormat};
+use crate::error::Result;
+use crate::layout::SaveLayout;
+use crate::constants::MARKER_LEN;
+use crate::provenance::{self, ProvenanceCheck};
+use crate::utils;
+
+/// Named part of a save, used to label differing byte ranges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Region {
+    Md5Header,
+    SavHeader,
+    /// Data between [SAV_HEADER] and hcd_section (first_8_bytes and middle_segment)
+    MiddleSegment,
+    /// Format marker (PC-ready only)
+    Marker,
+    /// Fill gap placing hcd_section at HCD_START_PC_READY (PC-ready only); zeros apart from the embedded leftovers or vault key
+    Fill,
+    /// Provenance record at the end of the fill gap (PC-ready only), rewritten on every packing
+    Provenance,
+    Hcd,
+    ZByte,
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Region::Md5Header => "MD5 header",
+            Region::SavHeader => "SAV header",
+            Region::MiddleSegment => "middle segment",
+            Region::Marker => "marker",
+            Region::Fill => "fill",
+            Region::Provenance => "provenance",
+            Region::Hcd => "HCD",
+            Region::ZByte => "Z byte",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Half-open byte range `start..end` that differs between two saves
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DiffRange {
+    pub region: Region,
+    pub start: usize,
+    pub end: usize,
+}
+
+/// Outcome of converting a save to the other format and back
+#[derive(Debug, Clone)]
+pub struct RoundTripReport {
+    /// Format of the input (and of the round-tripped result)
+    pub format: SaveFormat,
+    pub layout: &'static SaveLayout,
+    pub input_sha1: String,
+    pub intermediate_sha1: String,
+    pub result_sha1: String,
+    /// Differences between input and result, labelled by region of `format`
+    pub diffs: Vec<DiffRange>,
+    /// Warnings from both conversions
+    pub warnings: Vec<String>,
+    /// Provenance of PC-ready input
+    pub provenance: Option<ProvenanceCheck>,
+}
+
+impl RoundTripReport {
+    pub fn is_exact(&self) -> bool {
+        self.input_sha1 == self.result_sha1
+    }
+
+    /// Whether the only differences are in the provenance record, which packing writes anew
+    /// (an edited save, or one packed by another build, cannot get its old record back)
+    pub fn is_exact_but_provenance(&self) -> bool {
+        self.diffs.iter().all(|d| d.region == Region::Provenance)
+    }
+}
+
+/// Converts `data` to the other format and back entirely in memory, carrying leftovers across.
+/// For PC-ready input, `leftovers` are the ones kept from the original PS4 → PC-ready conversion.
+pub fn verify_round_trip(data: &[u8], leftovers: Option<&[u8]>) -> Result<RoundTripReport> {
+    let detected = detect::detect(data)?;
+    let layout = detected.layout;
+
+    let (there, back) = match detected.format {
+        SaveFormat::Ps4 => {
+            let there = conversion::ps4_to_pcready_with_layout(data, layout)?;
+            let back = conversion::convert_auto_with_layout(&there.data, layout, there.leftovers.as_deref(), LeftoversPolicy::default())?;
+            (there, back)
+        }
+        SaveFormat::PcReady => {
+            let there = conversion::convert_auto_with_layout(data, layout, leftovers, LeftoversPolicy::default())?;
+            let back = conversion::ps4_to_pcready_with_layout(&there.data, layout)?;
+            (there, back)
+        }
+    };
+
+    let mut warnings = there.warnings;
+    warnings.extend(back.warnings);
+    let provenance = match detected.format {
+        SaveFormat::Ps4 => None,
+        SaveFormat::PcReady => Some(provenance::check_provenance(data)?),
+    };
+
+    Ok(RoundTripReport {
+        format: detected.format,
+        layout,
+        input_sha1: utils::sha1_hex(data),
+        intermediate_sha1: utils::sha1_hex(&there.data),
+        result_sha1: utils::sha1_hex(&back.data),
+        diffs: diff_regions(data, &back.data, detected.format, layout),
+        warnings,
+        provenance,
+    })
+}
+
+/// Byte ranges where `a` and `b` differ, split at region boundaries of `format` in `layout`.
+/// Bytes present in only one of the two count as differing.
+pub fn diff_regions(a: &[u8], b: &[u8], format: SaveFormat, layout: &SaveLayout) -> Vec<DiffRange> {
+    let mut diffs: Vec<DiffRange> = Vec::new();
+    let len = a.len().max(b.len());
+
+    for i in 0..len {
+        if a.get(i) == b.get(i) {
+            continue;
+        }
+        let region = region_at(i, format, layout, len);
+        match diffs.last_mut() {
+            Some(last) if last.end == i && last.region == region => last.end = i + 1,
+            _ => diffs.push(DiffRange { region, start: i, end: i + 1 }),
+        }
+    }
+
+    diffs
+}
+
+/// Region containing `offset` in a save of `format` that is `len` bytes long
+pub fn region_at(offset: usize, format: SaveFormat, layout: &SaveLayout, len: usize) -> Region {
+    let md5 = layout.md5_header_size;
+    let sav = layout.sav_header_size;
+    let z_index = match format {
+        SaveFormat::Ps4 => len.saturating_sub(1),
+        SaveFormat::PcReady => len.saturating_sub(md5 + sav + 1),
+    };
+
+    match format {
+        SaveFormat::Ps4 => {
+            let hcd_start = md5 + sav + layout.hcd_start_in_middle();
+            if offset < md5 {
+                Region::Md5Header
+            } else if offset < md5 + sav {
+                Region::SavHeader
+            } else if offset < hcd_start {
+                Region::MiddleSegment
+            } else if offset < z_index {
+                Region::Hcd
+            } else {
+                Region::ZByte
+            }
+        }
+        SaveFormat::PcReady => {
+            let marker_end = layout.marker_offset + MARKER_LEN;
+            let fill_start = marker_end + layout.hcd_start_in_middle() - layout.marker_offset;
+            if offset < layout.marker_offset {
+                Region::MiddleSegment
+            } else if offset < marker_end {
+                Region::Marker
+            } else if offset < fill_start {
+                Region::MiddleSegment
+            } else if provenance::record_range(layout).is_some_and(|r| r.contains(&offset)) {
+                Region::Provenance
+            } else if offset < layout.hcd_start_pc_ready {
+                Region::Fill
+            } else if offset < z_index {
+                Region::Hcd
+            } else if offset == z_index {
+                Region::ZByte
+            } else if offset < z_index + 1 + sav {
+                Region::SavHeader
+            } else {
+                Region::Md5Header
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::conversion::ps4_to_pcready;
+    use crate::layout::DEFAULT_LAYOUT;
+    use crate::test_support::{random_tail, synthetic_ps4, TAIL_LEN};
+
+    /// The single range found after flipping the byte at `offset` of `data`
+    fn corrupt_one(data: &[u8], offset: usize, format: SaveFormat) -> Vec<DiffRange> {
+        let mut corrupted = data.to_vec();
+        corrupted[offset] ^= 0xFF;
+        diff_regions(data, &corrupted, format, &DEFAULT_LAYOUT)
+    }
+
+    #[test]
+    fn one_corrupt_byte_per_ps4_region() {
+        let ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        let hcd_start = 0x20 + 0x80 + DEFAULT_LAYOUT.hcd_start_in_middle();
+        for (offset, region) in [
+            (0x00, Region::Md5Header),
+            (0x1F, Region::Md5Header),
+            (0x20, Region::SavHeader),
+            (0x9F, Region::SavHeader),
+            (0xA0, Region::MiddleSegment),
+            (hcd_start - 1, Region::MiddleSegment),
+            (hcd_start, Region::Hcd),
+            (ps4.len() - 2, Region::Hcd),
+            (ps4.len() - 1, Region::ZByte),
+        ] {
+            assert_eq!(corrupt_one(&ps4, offset, SaveFormat::Ps4), vec![DiffRange { region, start: offset, end: offset + 1 }],
+                       "offset {offset:#X}");
+        }
+    }
+
+    #[test]
+    fn one_corrupt_byte_per_pcready_region() {
+        let pcready = ps4_to_pcready(&synthetic_ps4(&[0; TAIL_LEN])).unwrap().data;
+        let layout = DEFAULT_LAYOUT;
+        let provenance = provenance::record_range(&layout).unwrap();
+        let sav = layout.pcready_sav_header_offset();
+        for (offset, region) in [
+            (0x00, Region::MiddleSegment),
+            (layout.marker_offset, Region::Marker),
+            (layout.marker_offset + MARKER_LEN, Region::MiddleSegment),
+            (layout.hcd_start_pc_ready - 0x90, Region::Fill),
+            (provenance.start - 1, Region::Fill),
+            (provenance.start, Region::Provenance),
+            (provenance.end - 1, Region::Provenance),
+            (layout.hcd_start_pc_ready, Region::Hcd),
+            (sav - 2, Region::Hcd),
+            (sav - 1, Region::ZByte),
+            (sav, Region::SavHeader),
+            (sav + 0x7F, Region::SavHeader),
+            (sav + 0x80, Region::Md5Header),
+            (pcready.len() - 1, Region::Md5Header),
+        ] {
+            assert_eq!(corrupt_one(&pcready, offset, SaveFormat::PcReady), vec![DiffRange { region, start: offset, end: offset + 1 }],
+                       "offset {offset:#X}");
+        }
+    }
+
+    #[test]
+    fn adjacent_bytes_merge_within_a_region_and_split_at_its_edge() {
+        let ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        let mut corrupted = ps4.clone();
+        corrupted[0x1E..0x22].fill(0);
+        assert_eq!(diff_regions(&ps4, &corrupted, SaveFormat::Ps4, &DEFAULT_LAYOUT), vec![
+            DiffRange { region: Region::Md5Header, start: 0x1E, end: 0x20 },
+            DiffRange { region: Region::SavHeader, start: 0x20, end: 0x22 },
+        ]);
+    }
+
+    #[test]
+    fn missing_bytes_count_as_differing() {
+        let ps4 = synthetic_ps4(&[0; TAIL_LEN]);
+        let diffs = diff_regions(&ps4, &ps4[..ps4.len() - 1], SaveFormat::Ps4, &DEFAULT_LAYOUT);
+        assert_eq!(diffs, vec![DiffRange { region: Region::ZByte, start: ps4.len() - 1, end: ps4.len() }]);
+    }
+
+    #[test]
+    fn ps4_round_trip_is_exact() {
+        let ps4 = synthetic_ps4(&random_tail());
+        let report = verify_round_trip(&ps4, None).unwrap();
+        assert_eq!(report.format, SaveFormat::Ps4);
+        assert!(report.is_exact());
+        assert!(report.diffs.is_empty());
+        assert_eq!(report.provenance, None);
+    }
+
+    #[test]
+    fn pcready_round_trip_is_exact_with_its_leftovers() {
+        let packed = ps4_to_pcready(&synthetic_ps4(&random_tail())).unwrap();
+        let report = verify_round_trip(&packed.data, packed.leftovers.as_deref()).unwrap();
+        assert_eq!(report.format, SaveFormat::PcReady);
+        assert!(report.is_exact(), "{:?}", report.diffs);
+    }
+
+    #[test]
+    fn edited_pcready_differs_only_in_provenance() {
+        let mut pcready = ps4_to_pcready(&synthetic_ps4(&[0; TAIL_LEN])).unwrap().data;
+        pcready[0x1000] ^= 0xFF;
+        let report = verify_round_trip(&pcready, None).unwrap();
+        assert!(!report.is_exact());
+        assert!(report.is_exact_but_provenance());
+        assert!(report.diffs.iter().all(|d| provenance::record_range(&DEFAULT_LAYOUT).unwrap().contains(&d.start)));
+    }
+}
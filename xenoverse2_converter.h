@@ -9,46 +9,153 @@
 extern "C" {
 #endif
 
+/* Error codes returned by xv2_last_error() */
+#define XV2_OK                          0
+#define XV2_ERR_INVALID_ARGUMENT        1  /* NULL pointer passed */
+#define XV2_ERR_OUT_OF_MEMORY           2
+#define XV2_ERR_WRONG_SIZE              3  /* input is not the size the format requires */
+#define XV2_ERR_MISSING_MAGIC           4  /* '#SAV' not found where the layout requires it */
+#define XV2_ERR_UNRECOGNIZED_MARKER     5  /* no valid marker at 0x08 */
+#define XV2_ERR_UNSUPPORTED_VERSION     6  /* marker version cannot be unpacked */
+#define XV2_ERR_MARKER_LAYOUT_MISMATCH  7  /* marker present but layout sanity checks failed */
+#define XV2_ERR_LEFTOVERS_MISSING       8
+#define XV2_ERR_LEFTOVERS_SHORT         9
+#define XV2_ERR_INVALID_LAYOUT         10
+#define XV2_ERR_SIZE_MISMATCH          11  /* internal size check failed */
+#define XV2_ERR_LEFTOVERS_STORAGE      12  /* leftovers store could not save or load data */
+#define XV2_ERR_NO_MATCHING_LAYOUT     13  /* size and '#SAV'/marker positions match no known layout */
+#define XV2_ERR_EMBEDDED_LEFTOVERS_CORRUPT 14  /* leftovers record in the fill gap is damaged */
+#define XV2_ERR_LEFTOVERS_MISMATCH     15  /* leftovers damaged or from another save */
+
 /**
  * Converts a PS4 save file to PC-ready format
  * @param data Pointer to the input data
  * @param data_len Length of the input data
- * @param input_path Path to the input file (for reference)
- * @param dir Directory path
  * @param[out] out_len Length of the output data
- * @return Pointer to the converted data (must be freed with free_buffer)
+ * @param[out] out_leftovers Receives the leftovers file contents: header plus removed HCD tail bytes (must be freed with free_buffer), or NULL if none
+ * @param[out] out_leftovers_len Length of the leftovers data (0 if none)
+ * @return Pointer to the converted data (must be freed with free_buffer), or NULL on error (see xv2_last_error)
  */
-uint8_t* ps4_to_pcready_c(const uint8_t* data, size_t data_len, 
-                          const char* input_path, const char* dir, 
-                          size_t* out_len);
+uint8_t* ps4_to_pcready_c(const uint8_t* data, size_t data_len,
+                          size_t* out_len,
+                          uint8_t** out_leftovers, size_t* out_leftovers_len);
 
 /**
  * Converts a PC-ready save file to PS4 format
  * @param data Pointer to the input data
  * @param data_len Length of the input data
- * @param input_path Path to the input file (for reference)
- * @param dir Directory path
  * @param has_leftovers_flag Flag indicating if leftovers exist
+ * @param leftovers Leftovers returned by ps4_to_pcready_c, or NULL if not available
+ * @param leftovers_len Length of the leftovers data
  * @param[out] out_len Length of the output data
- * @return Pointer to the converted data (must be freed with free_buffer)
+ * @return Pointer to the converted data (must be freed with free_buffer), or NULL on error (see xv2_last_error)
  */
-uint8_t* pcready_to_ps4_c(const uint8_t* data, size_t data_len, 
-                          const char* input_path, const char* dir, 
-                          bool has_leftovers_flag, 
+uint8_t* pcready_to_ps4_c(const uint8_t* data, size_t data_len,
+                          bool has_leftovers_flag,
+                          const uint8_t* leftovers, size_t leftovers_len,
                           size_t* out_len);
 
 /**
  * Automatically detects the format and converts accordingly
  * @param data Pointer to the input data
  * @param data_len Length of the input data
- * @param input_path Path to the input file (for reference)
- * @param dir Directory path
+ * @param leftovers Leftovers returned by ps4_to_pcready_c, or NULL if not available
+ * @param leftovers_len Length of the leftovers data
  * @param[out] out_len Length of the output data
- * @return Pointer to the converted data (must be freed with free_buffer)
+ * @param[out] out_leftovers When a PS4 save is packed: receives the leftovers file as for ps4_to_pcready_c
+ *             (must be freed with free_buffer), or NULL if none. Always NULL when unpacking.
+ * @param[out] out_leftovers_len Length of the leftovers data (0 if none)
+ * @return Pointer to the converted data (must be freed with free_buffer), or NULL on error (see xv2_last_error)
+ */
+uint8_t* convert_auto_c(const uint8_t* data, size_t data_len,
+                        const uint8_t* leftovers, size_t leftovers_len,
+                        size_t* out_len,
+                        uint8_t** out_leftovers, size_t* out_leftovers_len);
+
+/* What unpacking does when the marker flags leftovers but they are missing or too short.
+   pcready_to_ps4_c and convert_auto_c use XV2_LEFTOVERS_ZERO_FILL. */
+#define XV2_LEFTOVERS_ZERO_FILL 0  /* fill the missing bytes with zeros */
+#define XV2_LEFTOVERS_STRICT    1  /* fail with XV2_ERR_LEFTOVERS_MISSING / XV2_ERR_LEFTOVERS_SHORT */
+
+/**
+ * pcready_to_ps4_c with a leftovers policy
+ * @param policy XV2_LEFTOVERS_*
+ */
+uint8_t* pcready_to_ps4_policy_c(const uint8_t* data, size_t data_len,
+                                 bool has_leftovers_flag,
+                                 const uint8_t* leftovers, size_t leftovers_len,
+                                 int32_t policy,
+                                 size_t* out_len);
+
+/**
+ * convert_auto_c with a leftovers policy
+ * @param policy XV2_LEFTOVERS_*
+ */
+uint8_t* convert_auto_policy_c(const uint8_t* data, size_t data_len,
+                               const uint8_t* leftovers, size_t leftovers_len,
+                               int32_t policy,
+                               size_t* out_len,
+                               uint8_t** out_leftovers, size_t* out_leftovers_len);
+
+#define XV2_FORMAT_PS4      0
+#define XV2_FORMAT_PC_READY 1
+#define XV2_MAX_SAV_OFFSETS 8
+
+#define XV2_PROVENANCE_MISSING    0  /* no provenance record (older build, or cleared by an editor) */
+#define XV2_PROVENANCE_UNMODIFIED 1
+#define XV2_PROVENANCE_MODIFIED   2  /* changed since it was packed */
+
+/** Description of a save, filled by inspect_c */
+typedef struct {
+    int32_t format;                        /* XV2_FORMAT_* */
+    char layout_name[32];                  /* game-version layout that matched */
+    size_t size;
+    int32_t marker_version;                /* -1 when there is no marker (PS4) */
+    int32_t leftovers_flag;                /* -1 when there is no marker (PS4) */
+    uint8_t z_byte;
+    size_t sav_offsets[XV2_MAX_SAV_OFFSETS]; /* first '#SAV' offsets found */
+    size_t sav_offset_count;               /* total number of '#SAV' occurrences */
+    size_t fill_len;                       /* fill gap before the HCD in PC-ready layout */
+    size_t excess;                         /* HCD tail bytes trimmed when packing */
+    size_t pad;                            /* zero bytes appended when packing */
+    bool leftovers;                        /* PS4: packing produces leftovers; PC-ready: unpacking needs them */
+    char sha1[41];
+    char converted_sha1[41];               /* empty when the converted save depends on leftovers */
+    bool embedded;                         /* trimmed HCD tail kept in the fill gap (marker v3) */
+    int32_t provenance;                    /* XV2_PROVENANCE_*, -1 for PS4 saves */
+    char packed_from_sha1[41];             /* PS4 SHA1 named by the provenance record, empty without one */
+    char conversion_error[256];            /* why the save does not convert (converted_sha1 is empty then), else empty */
+} Xv2SaveInfo;
+
+/**
+ * Describes a save without converting it
+ * @param data Pointer to the input data
+ * @param data_len Length of the input data
+ * @param[out] out_info Receives the description
+ * @return true on success, false on error (see xv2_last_error)
+ */
+bool inspect_c(const uint8_t* data, size_t data_len, Xv2SaveInfo* out_info);
+
+/**
+ * Checks the MD5 header of a PS4 save against the body it is assumed to cover (unverified: the header format
+ * is inferred, not documented)
+ * @param data Pointer to the PS4 save data
+ * @param data_len Length of the data
+ * @return 1 if the stored digest matches, 0 if it does not, -1 on error (see xv2_last_error)
+ */
+int32_t check_md5_header_c(const uint8_t* data, size_t data_len);
+
+/**
+ * Returns the error code of the last failed call on the calling thread
+ * @return One of the XV2_ERR_* codes, or XV2_OK if the last call succeeded
+ */
+int32_t xv2_last_error(void);
+
+/**
+ * Returns a human-readable message for the last failed call on the calling thread
+ * @return NUL-terminated string, valid until the next call on the same thread (do not free)
  */
-uint8_t* convert_auto_c(const uint8_t* data, size_t data_len, 
-                        const char* input_path, const char* dir, 
-                        size_t* out_len);
+const char* xv2_last_error_message(void);
 
 /**
  * Frees memory allocated by the conversion functions